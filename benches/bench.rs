@@ -2,7 +2,24 @@ use std::convert::TryInto;
 
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use num_bigint::RandBigInt;
-use prime_factorization::{factorization, gen_prime, is_prime};
+use prime_factorization::{
+    bpsw_test, factorization, gen_prime, is_prime, prime_pi, Montgomery128, Montgomery64,
+};
+use rand::Rng;
+
+#[allow(clippy::manual_range_contains, clippy::assign_op_pattern)]
+mod u256 {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+
+use u256::U256;
+
+// The crate's U256 widening mul_mod, kept here as the baseline for Montgomery.
+fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+    (U256::from(a) * U256::from(b) % U256::from(m)).as_u128()
+}
 
 fn millar_rabin_bench(c: &mut Criterion) {
     let mut rng = rand::thread_rng();
@@ -20,6 +37,61 @@ fn millar_rabin_bench(c: &mut Criterion) {
     }
 }
 
+fn bpsw_bench(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+
+    let cases = [16, 32, 64, 128, 1024, 2048, 4096];
+
+    for &bits in cases.iter() {
+        c.bench_function(&format!("bpsw {}bit", bits), |b| {
+            b.iter_batched(
+                || rng.gen_biguint(bits),
+                |n| bpsw_test(&n),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+fn mul_mod_bench(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let n64 = rng.gen::<u64>() | 1;
+    let n128 = rng.gen::<u128>() | 1;
+    let x64 = rng.gen::<u64>() % n64;
+    let x128 = rng.gen::<u128>() % n128;
+
+    c.bench_function("mul_mod u256 64bit", |b| {
+        let mut x = x64 as u128;
+        b.iter(|| {
+            x = mul_mod(x, x, n64 as u128);
+            x
+        })
+    });
+    c.bench_function("mul_mod montgomery 64bit", |b| {
+        let m = Montgomery64::new(n64);
+        let mut x = m.to_montgomery(x64);
+        b.iter(|| {
+            x = m.mul(x, x);
+            x
+        })
+    });
+    c.bench_function("mul_mod u256 128bit", |b| {
+        let mut x = x128;
+        b.iter(|| {
+            x = mul_mod(x, x, n128);
+            x
+        })
+    });
+    c.bench_function("mul_mod montgomery 128bit", |b| {
+        let m = Montgomery128::new(n128);
+        let mut x = m.to_montgomery(x128);
+        b.iter(|| {
+            x = m.mul(x, x);
+            x
+        })
+    });
+}
+
 fn gen_semiprime(bits: usize) -> u128 {
     let mut rng = rand::thread_rng();
     (gen_prime(bits, &mut rng) * gen_prime(bits, &mut rng))
@@ -28,18 +100,30 @@ fn gen_semiprime(bits: usize) -> u128 {
 }
 
 fn factorization_bench(c: &mut Criterion) {
-    let cases = [8, 16, 24, 32, 40, 48];
+    let cases = [8, 16, 24, 32, 40, 48, 56];
 
     for &bits in cases.iter() {
         c.bench_function(&format!("factor semiprime {}bit", bits * 2), |b| {
-            b.iter_batched(
-                || gen_semiprime(bits),
-                |n| factorization(n),
-                BatchSize::LargeInput,
-            )
+            b.iter_batched(|| gen_semiprime(bits), factorization, BatchSize::LargeInput)
         });
     }
 }
 
-criterion_group!(benches, millar_rabin_bench, factorization_bench);
+fn prime_pi_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prime_pi");
+    group.sample_size(10);
+    for &k in [12, 14, 15].iter() {
+        group.bench_function(format!("1e{}", k), |b| b.iter(|| prime_pi(10u64.pow(k))));
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    millar_rabin_bench,
+    bpsw_bench,
+    mul_mod_bench,
+    factorization_bench,
+    prime_pi_bench
+);
 criterion_main!(benches);
@@ -0,0 +1,289 @@
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::{factor_range, factorize, factorize_biguint, Factorization};
+
+// 0 has no factorization, so every function panics on it with this message,
+// and the _range versions skip it.
+const ZERO_MESSAGE: &str = "arithmetic functions are not defined at 0";
+
+fn factorization(n: u128) -> Factorization {
+    assert!(n > 0, "{}", ZERO_MESSAGE);
+    factorize(n)
+}
+
+pub fn euler_phi(n: u128) -> u128 {
+    phi(&factorization(n))
+}
+
+pub fn carmichael_lambda(n: u128) -> u128 {
+    lambda(&factorization(n))
+}
+
+pub fn divisor_count(n: u128) -> u64 {
+    tau(&factorization(n))
+}
+
+// sigma_k(n), the sum of the k-th powers of the divisors, or None if it does
+// not fit in u128.
+pub fn divisor_sigma(n: u128, k: u32) -> Option<u128> {
+    sigma(&factorization(n), k)
+}
+
+pub fn moebius(n: u128) -> i32 {
+    mu(&factorization(n))
+}
+
+pub fn liouville(n: u128) -> i32 {
+    liouville_of(&factorization(n))
+}
+
+pub fn radical(n: u128) -> u128 {
+    factorization(n).radical().value().unwrap()
+}
+
+fn phi(f: &Factorization) -> u128 {
+    f.iter().map(|(p, e)| p.pow(e - 1) * (p - 1)).product()
+}
+
+fn lambda(f: &Factorization) -> u128 {
+    f.iter()
+        .map(|(p, e)| match (p, e) {
+            // (Z/2^e)^* is cyclic of order 2^(e-1) only up to e = 2.
+            (2, e) if e >= 3 => 1 << (e - 2),
+            (p, e) => p.pow(e - 1) * (p - 1),
+        })
+        .fold(1, |acc, x| acc.lcm(&x))
+}
+
+fn tau(f: &Factorization) -> u64 {
+    f.iter().map(|(_, e)| e as u64 + 1).product()
+}
+
+fn sigma(f: &Factorization, k: u32) -> Option<u128> {
+    f.iter().try_fold(1u128, |acc, (p, e)| {
+        let pk = p.checked_pow(k)?;
+        // 1 + p^k + ... + p^ek
+        let mut sum = 1u128;
+        let mut term = 1u128;
+        for _ in 0..e {
+            term = term.checked_mul(pk)?;
+            sum = sum.checked_add(term)?;
+        }
+        acc.checked_mul(sum)
+    })
+}
+
+fn mu(f: &Factorization) -> i32 {
+    if f.is_square_free() {
+        1 - 2 * (f.len() as i32 % 2)
+    } else {
+        0
+    }
+}
+
+fn liouville_of(f: &Factorization) -> i32 {
+    let omega: u32 = f.iter().map(|(_, e)| e).sum();
+    1 - 2 * (omega % 2) as i32
+}
+
+fn prime_powers_biguint(n: &BigUint) -> Vec<(BigUint, u32)> {
+    assert!(!n.is_zero(), "{}", ZERO_MESSAGE);
+    // factorize_biguint returns the primes sorted.
+    let mut ret: Vec<(BigUint, u32)> = vec![];
+    for p in factorize_biguint(n) {
+        match ret.last_mut() {
+            Some((q, e)) if *q == p => *e += 1,
+            _ => ret.push((p, 1)),
+        }
+    }
+    ret
+}
+
+pub fn euler_phi_biguint(n: &BigUint) -> BigUint {
+    prime_powers_biguint(n)
+        .iter()
+        .map(|(p, e)| p.pow(e - 1) * (p - 1u32))
+        .product()
+}
+
+pub fn carmichael_lambda_biguint(n: &BigUint) -> BigUint {
+    prime_powers_biguint(n)
+        .iter()
+        .map(|(p, e)| {
+            if p == &BigUint::from(2u32) && *e >= 3 {
+                BigUint::one() << (e - 2)
+            } else {
+                p.pow(e - 1) * (p - 1u32)
+            }
+        })
+        .fold(BigUint::one(), |acc, x| acc.lcm(&x))
+}
+
+pub fn divisor_count_biguint(n: &BigUint) -> u64 {
+    prime_powers_biguint(n)
+        .iter()
+        .map(|&(_, e)| e as u64 + 1)
+        .product()
+}
+
+pub fn divisor_sigma_biguint(n: &BigUint, k: u32) -> BigUint {
+    prime_powers_biguint(n)
+        .iter()
+        .map(|(p, e)| {
+            let pk = p.pow(k);
+            let mut sum = BigUint::one();
+            let mut term = BigUint::one();
+            for _ in 0..*e {
+                term *= &pk;
+                sum += &term;
+            }
+            sum
+        })
+        .product()
+}
+
+pub fn moebius_biguint(n: &BigUint) -> i32 {
+    let f = prime_powers_biguint(n);
+    if f.iter().all(|&(_, e)| e == 1) {
+        1 - 2 * (f.len() as i32 % 2)
+    } else {
+        0
+    }
+}
+
+pub fn liouville_biguint(n: &BigUint) -> i32 {
+    let omega: u32 = prime_powers_biguint(n).iter().map(|&(_, e)| e).sum();
+    1 - 2 * (omega % 2) as i32
+}
+
+pub fn radical_biguint(n: &BigUint) -> BigUint {
+    prime_powers_biguint(n)
+        .into_iter()
+        .map(|(p, _)| p)
+        .product()
+}
+
+pub fn euler_phi_range(lo: u64, hi: u64) -> impl Iterator<Item = (u64, u64)> {
+    factor_range(lo, hi).map(|(n, f)| (n, phi(&f) as u64))
+}
+
+pub fn carmichael_lambda_range(lo: u64, hi: u64) -> impl Iterator<Item = (u64, u64)> {
+    factor_range(lo, hi).map(|(n, f)| (n, lambda(&f) as u64))
+}
+
+pub fn divisor_count_range(lo: u64, hi: u64) -> impl Iterator<Item = (u64, u64)> {
+    factor_range(lo, hi).map(|(n, f)| (n, tau(&f)))
+}
+
+pub fn divisor_sigma_range(lo: u64, hi: u64, k: u32) -> impl Iterator<Item = (u64, Option<u128>)> {
+    factor_range(lo, hi).map(move |(n, f)| (n, sigma(&f, k)))
+}
+
+pub fn moebius_range(lo: u64, hi: u64) -> impl Iterator<Item = (u64, i32)> {
+    factor_range(lo, hi).map(|(n, f)| (n, mu(&f)))
+}
+
+pub fn liouville_range(lo: u64, hi: u64) -> impl Iterator<Item = (u64, i32)> {
+    factor_range(lo, hi).map(|(n, f)| (n, liouville_of(&f)))
+}
+
+pub fn radical_range(lo: u64, hi: u64) -> impl Iterator<Item = (u64, u64)> {
+    factor_range(lo, hi).map(|(n, f)| (n, f.radical().value().unwrap() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn small_values() {
+        let phi: Vec<u128> = (1..=12).map(euler_phi).collect();
+        assert_eq!(phi, [1, 1, 2, 2, 4, 2, 6, 4, 6, 4, 10, 4]);
+        let lambda: Vec<u128> = (1..=16).map(carmichael_lambda).collect();
+        assert_eq!(lambda, [1, 1, 2, 2, 4, 2, 6, 2, 6, 4, 10, 2, 12, 6, 4, 4]);
+        let tau: Vec<u64> = (1..=12).map(divisor_count).collect();
+        assert_eq!(tau, [1, 2, 2, 3, 2, 4, 2, 4, 3, 4, 2, 6]);
+        let sigma: Vec<u128> = (1..=12).map(|n| divisor_sigma(n, 1).unwrap()).collect();
+        assert_eq!(sigma, [1, 3, 4, 7, 6, 12, 8, 15, 13, 18, 12, 28]);
+        assert_eq!(divisor_sigma(12, 2), Some(210));
+        assert_eq!(divisor_sigma(12, 0), Some(6));
+        let mu: Vec<i32> = (1..=12).map(moebius).collect();
+        assert_eq!(mu, [1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0]);
+        let liouville: Vec<i32> = (1..=12).map(liouville).collect();
+        assert_eq!(liouville, [1, -1, -1, 1, -1, 1, -1, -1, 1, 1, -1, -1]);
+        assert_eq!(radical(360), 30);
+
+        // sigma(2^127 - 1) = 2^127 fits, sigma(2^127) = 2^128 - 1 too, but
+        // sigma_2 of either does not.
+        let m = (1u128 << 127) - 1;
+        assert_eq!(divisor_sigma(m, 1), Some(1 << 127));
+        assert_eq!(divisor_sigma(1 << 127, 1), Some(u128::MAX));
+        assert_eq!(divisor_sigma(m, 2), None);
+        assert_eq!(euler_phi(m), m - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "arithmetic functions are not defined at 0")]
+    fn zero() {
+        euler_phi(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "arithmetic functions are not defined at 0")]
+    fn zero_biguint() {
+        divisor_sigma_biguint(&BigUint::from(0u32), 1);
+    }
+
+    #[test]
+    fn biguint_and_ranges() {
+        for n in 1..300u64 {
+            let b = BigUint::from(n);
+            let n = n as u128;
+            assert_eq!(euler_phi_biguint(&b), euler_phi(n).into());
+            assert_eq!(carmichael_lambda_biguint(&b), carmichael_lambda(n).into());
+            assert_eq!(divisor_count_biguint(&b), divisor_count(n));
+            assert_eq!(
+                divisor_sigma_biguint(&b, 3),
+                divisor_sigma(n, 3).unwrap().into()
+            );
+            assert_eq!(moebius_biguint(&b), moebius(n));
+            assert_eq!(liouville_biguint(&b), liouville(n));
+            assert_eq!(radical_biguint(&b), radical(n).into());
+        }
+        // (2^89 - 1)^2 (2^61 - 1)
+        let p = (BigUint::from(1u32) << 89) - 1u32;
+        let q = (BigUint::from(1u32) << 61) - 1u32;
+        let n = &p * &p * &q;
+        assert_eq!(euler_phi_biguint(&n), &p * (&p - 1u32) * (&q - 1u32));
+        assert_eq!(divisor_count_biguint(&n), 6);
+        assert_eq!(moebius_biguint(&n), 0);
+        assert_eq!(radical_biguint(&n), &p * &q);
+
+        assert_eq!(euler_phi_range(0, 3).collect::<Vec<_>>(), [(1, 1), (2, 1)]);
+
+        let (lo, hi) = (1_000_000_000_000u64, 1_000_000_001_000);
+        let phi: Vec<_> = euler_phi_range(lo, hi).collect();
+        assert_eq!(phi.len(), 1000);
+        for &(n, x) in phi.iter().step_by(97) {
+            assert_eq!(x as u128, euler_phi(n as u128));
+        }
+        let ranges = carmichael_lambda_range(1, 100)
+            .zip(divisor_count_range(1, 100))
+            .zip(divisor_sigma_range(1, 100, 1))
+            .zip(moebius_range(1, 100))
+            .zip(liouville_range(1, 100))
+            .zip(radical_range(1, 100));
+        for (((((l, t), s), m), li), r) in ranges {
+            let n = l.0 as u128;
+            assert_eq!(l.1 as u128, carmichael_lambda(n));
+            assert_eq!(t.1, divisor_count(n));
+            assert_eq!(s.1, divisor_sigma(n, 1));
+            assert_eq!(m.1, moebius(n));
+            assert_eq!(li.1, liouville(n));
+            assert_eq!(r.1 as u128, radical(n));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+// Screens RSA moduli for the classic ways a key can be factored cheaply.
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+use crate::{batch_gcd, pollard_p_minus_1_biguint, sieve::primes_up_to};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Weakness {
+    // A prime shared with another modulus of the batch.
+    SharedFactor,
+    SmallFactor,
+    // |p - q| is small enough for Fermat's method.
+    CloseFactors,
+    // p - 1 is smooth for Pollard's p - 1.
+    SmoothPMinus1,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AuditFinding {
+    pub index: usize,
+    pub weakness: Weakness,
+    // p <= q and p * q = n.
+    pub p: BigUint,
+    pub q: BigUint,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AuditOptions {
+    pub trial_division_bound: u64,
+    pub fermat_steps: u64,
+    pub p_minus_1_b1: u64,
+    pub p_minus_1_b2: u64,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        AuditOptions {
+            trial_division_bound: 1 << 16,
+            fermat_steps: 1 << 16,
+            p_minus_1_b1: 10_000,
+            p_minus_1_b2: 1_000_000,
+        }
+    }
+}
+
+// The factorable moduli, each with the first check that split it.
+pub fn audit_moduli(moduli: &[BigUint], options: &AuditOptions) -> Vec<AuditFinding> {
+    let primes = primes_up_to(options.trial_division_bound);
+    let shared = batch_gcd(moduli);
+    moduli
+        .iter()
+        .zip(shared)
+        .enumerate()
+        .filter_map(|(index, (n, g))| {
+            let g = match g {
+                // Both primes are shared, so one of the other moduli splits n
+                // on its own. Exact duplicates never do.
+                Some(g) if &g == n => moduli
+                    .iter()
+                    .filter(|&m| m != n)
+                    .map(|m| n.gcd(m))
+                    .find(|g| !g.is_one() && g != n),
+                g => g,
+            };
+            let (weakness, p) = match g {
+                Some(g) => (Weakness::SharedFactor, g),
+                None => find_weakness(n, &primes, options)?,
+            };
+            let q = n / &p;
+            let (p, q) = if p <= q { (p, q) } else { (q, p) };
+            Some(AuditFinding {
+                index,
+                weakness,
+                p,
+                q,
+            })
+        })
+        .collect()
+}
+
+fn find_weakness(
+    n: &BigUint,
+    primes: &[u64],
+    options: &AuditOptions,
+) -> Option<(Weakness, BigUint)> {
+    if n <= &BigUint::one() {
+        return None;
+    }
+    if let Some(&p) = primes
+        .iter()
+        .find(|&&p| (n % p).is_zero() && n != &p.into())
+    {
+        return Some((Weakness::SmallFactor, p.into()));
+    }
+    if let Some(p) = fermat_factor(n, options.fermat_steps) {
+        return Some((Weakness::CloseFactors, p));
+    }
+    pollard_p_minus_1_biguint(n, options.p_minus_1_b1, options.p_minus_1_b2)
+        .filter(|p| p < n)
+        .map(|p| (Weakness::SmoothPMinus1, p))
+}
+
+// Fermat's method: looks for n = a^2 - b^2 with a going up from ceil(sqrt(n)),
+// which succeeds within (p - q)^2 / (8 sqrt(n)) steps for odd n = p q.
+pub fn fermat_factor(n: &BigUint, steps: u64) -> Option<BigUint> {
+    if n.is_even() {
+        return if n > &BigUint::from(2u32) {
+            Some(2u32.into())
+        } else {
+            None
+        };
+    }
+    let mut a = n.sqrt();
+    if &(&a * &a) < n {
+        a += 1u32;
+    }
+    let mut b2 = &a * &a - n;
+    for _ in 0..steps {
+        let b = b2.sqrt();
+        if &b * &b == b2 {
+            let p = &a - &b;
+            return if p.is_one() { None } else { Some(p) };
+        }
+        b2 += (&a << 1) + 1u32;
+        a += 1u32;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn fermat() {
+        let p = BigUint::from(1000000007u32);
+        let q = BigUint::from(1000000009u32);
+        assert_eq!(fermat_factor(&(&p * &q), 1), Some(p.clone()));
+        assert_eq!(fermat_factor(&(&p * &p), 1), Some(p));
+        assert_eq!(fermat_factor(&BigUint::from(13u32), 100), None);
+    }
+
+    #[test]
+    fn audit() {
+        let mut rng = rand::thread_rng();
+        let mut big = || gen_prime_exact_bits(128, TopBits::Two, &mut rng);
+        let primes: Vec<BigUint> = (0..6).map(|_| big()).collect();
+
+        let strong = &primes[0] * &primes[1];
+
+        let p = &primes[2];
+        let close_q = gen_prime_in_range(&(p + 1u32), &(p + (1u64 << 40)), &mut rng).unwrap();
+        let close = p * &close_q;
+
+        let small = &primes[3] * 65521u32;
+
+        // p - 1 = 2^4 * 3 * 5 * 11 * 29 * 151 * 86501.
+        let smooth_p = BigUint::from(1000000000561u64);
+        let smooth = &smooth_p * &primes[4];
+
+        // Every prime of these and of `strong` is shared, so their batch gcds
+        // are the moduli themselves.
+        let shared_a = &primes[0] * &primes[5];
+        let shared_b = &primes[1] * &primes[5];
+
+        let moduli = [
+            strong,
+            close.clone(),
+            small,
+            smooth,
+            shared_a,
+            shared_b,
+            close,
+        ];
+        let options = AuditOptions {
+            p_minus_1_b1: 1000,
+            p_minus_1_b2: 100000,
+            ..AuditOptions::default()
+        };
+        let findings = audit_moduli(&moduli, &options);
+        let sorted = |a: &BigUint, b: &BigUint| {
+            if a < b {
+                (a.clone(), b.clone())
+            } else {
+                (b.clone(), a.clone())
+            }
+        };
+        let expected = [
+            (0, Weakness::SharedFactor, sorted(&primes[0], &primes[1])),
+            (1, Weakness::CloseFactors, (p.clone(), close_q.clone())),
+            (
+                2,
+                Weakness::SmallFactor,
+                (65521u32.into(), primes[3].clone()),
+            ),
+            (3, Weakness::SmoothPMinus1, (smooth_p, primes[4].clone())),
+            (4, Weakness::SharedFactor, sorted(&primes[0], &primes[5])),
+            (5, Weakness::SharedFactor, sorted(&primes[1], &primes[5])),
+            (6, Weakness::CloseFactors, (p.clone(), close_q.clone())),
+        ];
+        assert_eq!(findings.len(), expected.len());
+        for (f, (index, weakness, pq)) in findings.iter().zip(expected.iter()) {
+            assert_eq!(
+                (f.index, f.weakness, &(f.p.clone(), f.q.clone())),
+                (*index, *weakness, pq)
+            );
+        }
+
+        // 101 * 103, 103 * 107, 131 * 101
+        let moduli = [10403u32, 11021, 13231].map(BigUint::from);
+        let findings = audit_moduli(&moduli, &AuditOptions::default());
+        assert_eq!(
+            (findings[0].weakness, &findings[0].p, &findings[0].q),
+            (Weakness::SharedFactor, &101u32.into(), &103u32.into())
+        );
+    }
+}
@@ -0,0 +1,112 @@
+// Bernstein's batch GCD: gcd(n_i, prod_{j != i} n_j) for every i from one
+// product tree and one remainder tree, in quasi-linear time.
+
+use std::thread;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+// None where n_i is coprime to all the other moduli. A gcd equal to n_i means
+// every prime of n_i is shared, e.g. with a duplicate.
+pub fn batch_gcd(moduli: &[BigUint]) -> Vec<Option<BigUint>> {
+    assert!(
+        moduli.iter().all(|n| !n.is_zero()),
+        "moduli must be nonzero"
+    );
+    if moduli.is_empty() {
+        return vec![];
+    }
+
+    let mut levels = vec![moduli.to_vec()];
+    while levels.last().unwrap().len() > 1 {
+        let pairs: Vec<&[BigUint]> = levels.last().unwrap().chunks(2).collect();
+        let next = par_map(&pairs, |pair| match pair {
+            [a, b] => a * b,
+            _ => pair[0].clone(),
+        });
+        levels.push(next);
+    }
+
+    // Going down, each node gets the root product modulo its own square.
+    let mut rems = levels.pop().unwrap();
+    while let Some(level) = levels.pop() {
+        let nodes: Vec<(usize, &BigUint)> = level.iter().enumerate().collect();
+        rems = par_map(&nodes, |&(i, n)| &rems[i / 2] % (n * n));
+    }
+
+    let nodes: Vec<(&BigUint, &BigUint)> = moduli.iter().zip(rems.iter()).collect();
+    par_map(&nodes, |&(n, r)| {
+        let g = (r / n).gcd(n);
+        if g.is_one() {
+            None
+        } else {
+            Some(g)
+        }
+    })
+}
+
+// Maps one tree level across the available cores.
+fn par_map<T: Sync, U: Send>(items: &[T], f: impl Fn(&T) -> U + Sync) -> Vec<U> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    if threads == 1 || items.len() < 2 {
+        return items.iter().map(f).collect();
+    }
+    let chunk = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|s| {
+        let handles: Vec<_> = items
+            .chunks(chunk)
+            .map(|c| s.spawn(move || c.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn shared_factors() {
+        let n = |x: u64| BigUint::from(x);
+        // 101 * 103, 103 * 107, 109 * 113, 127 * 131, 131 * 101, 137 * 139 twice
+        let moduli = [10403, 11021, 12317, 16637, 13231, 19043, 19043].map(n);
+        assert_eq!(
+            batch_gcd(&moduli),
+            [
+                Some(n(10403)),
+                Some(n(103)),
+                None,
+                Some(n(131)),
+                Some(n(13231)),
+                Some(n(19043)),
+                Some(n(19043))
+            ]
+        );
+        assert_eq!(batch_gcd(&[n(15)]), [None]);
+        assert!(batch_gcd(&[]).is_empty());
+
+        let mut rng = rand::thread_rng();
+        let primes: Vec<BigUint> = (0..65)
+            .map(|_| gen_prime_exact_bits(256, TopBits::Two, &mut rng))
+            .collect();
+        let mut moduli: Vec<BigUint> = primes
+            .windows(2)
+            .step_by(2)
+            .map(|w| &w[0] * &w[1])
+            .collect();
+        moduli.push(&primes[3] * &primes[64]);
+        let gcds = batch_gcd(&moduli);
+        for (i, g) in gcds.iter().enumerate() {
+            match i {
+                1 | 32 => assert_eq!(g.as_ref(), Some(&primes[3])),
+                _ => assert_eq!(g, &None),
+            }
+        }
+    }
+}
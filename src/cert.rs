@@ -0,0 +1,324 @@
+// Primality certificates. Generation leans on the factoring and probable
+// prime code, but `verify_certificate` only uses modular arithmetic, so a
+// certificate can be checked without trusting any of it.
+
+use std::convert::TryFrom;
+
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::Rng;
+
+use crate::{
+    bpsw_test, ecm_with_rng, ecpp::verify_ecpp_step, is_prime_u128, sieve::primes_up_to,
+    try_factorization_with_rng, Montgomery128,
+};
+
+// Primes below this are certified by trial division.
+const SMALL_BOUND: u64 = 1 << 32;
+
+// n - 1 is trial divided this far before cofactors are split with ECM.
+const TRIAL_BOUND: u64 = 1 << 16;
+
+const MAX_WITNESS: u64 = 1000;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Certificate {
+    Small(u64),
+    // witness has order n - 1, which is fully factored.
+    Pratt {
+        n: u128,
+        witness: u128,
+        factors: Vec<Certificate>,
+    },
+    // Pocklington / Brillhart–Lehmer–Selfridge: the certified primes q, with
+    // their full powers in n - 1, make up a part F >= n^(1/3), and each
+    // witness a satisfies a^(n-1) = 1 and gcd(a^((n-1)/q) - 1, n) = 1.
+    Pocklington {
+        n: BigUint,
+        factors: Vec<(Certificate, BigUint)>,
+    },
+    // Atkin–Morain: (x, y) lies on y^2 = x^3 + ax + b over Z/nZ, m is a
+    // multiple of the prime q > (n^(1/4) + 1)^2, and [m/q](x, y) has order q.
+    Ecpp {
+        n: BigUint,
+        a: BigUint,
+        b: BigUint,
+        m: BigUint,
+        x: BigUint,
+        y: BigUint,
+        q: Box<Certificate>,
+    },
+}
+
+impl Certificate {
+    pub fn n(&self) -> BigUint {
+        match self {
+            Certificate::Small(n) => (*n).into(),
+            Certificate::Pratt { n, .. } => (*n).into(),
+            Certificate::Pocklington { n, .. } => n.clone(),
+            Certificate::Ecpp { n, .. } => n.clone(),
+        }
+    }
+}
+
+pub fn pratt_certificate(n: u128) -> Option<Certificate> {
+    pratt_certificate_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn pratt_certificate_with_rng(n: u128, rng: &mut impl Rng) -> Option<Certificate> {
+    if !is_prime_u128(n) {
+        return None;
+    }
+    if n < SMALL_BOUND as u128 {
+        return Some(Certificate::Small(n as u64));
+    }
+
+    let mut qs = try_factorization_with_rng(n - 1, rng).ok()?;
+    qs.dedup();
+    let m = Montgomery128::new(n);
+    let witness = (2..MAX_WITNESS as u128).find(|&a| {
+        let a = m.to_montgomery(a);
+        qs.iter().all(|&q| m.pow(a, (n - 1) / q) != m.one())
+    })?;
+    let factors = qs
+        .iter()
+        .map(|&q| pratt_certificate_with_rng(q, rng))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Certificate::Pratt {
+        n,
+        witness,
+        factors,
+    })
+}
+
+pub fn pocklington_certificate(n: &BigUint) -> Option<Certificate> {
+    pocklington_certificate_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn pocklington_certificate_with_rng(n: &BigUint, rng: &mut impl Rng) -> Option<Certificate> {
+    if let Ok(n) = u128::try_from(n) {
+        return pratt_certificate_with_rng(n, rng);
+    }
+    if n.is_even() || !bpsw_test(n) {
+        return None;
+    }
+
+    let n1: BigUint = n - 1u32;
+    let mut rest = n1.clone();
+    let mut factors = vec![];
+    for p in primes_up_to(TRIAL_BOUND) {
+        if (&rest % p).is_zero() {
+            while (&rest % p).is_zero() {
+                rest /= p;
+            }
+            factors.push((Certificate::Small(p), pocklington_witness(n, &p.into())?));
+        }
+    }
+
+    // Split cofactors until the certified part is large enough, smallest first.
+    let mut cofactors = if rest.is_one() { vec![] } else { vec![rest] };
+    while !large_enough(n, &factored_part(&n1, &factors)) {
+        cofactors.sort_by(|a, b| b.cmp(a));
+        let c = cofactors.pop()?;
+        if bpsw_test(&c) {
+            // Take the full power of c out of the other cofactors, so that a
+            // repeated prime is only certified once.
+            for x in cofactors.iter_mut() {
+                while (&*x % &c).is_zero() {
+                    *x /= &c;
+                }
+            }
+            cofactors.retain(|x| !x.is_one());
+            if let Some(cert) = pocklington_certificate_with_rng(&c, rng) {
+                factors.push((cert, pocklington_witness(n, &c)?));
+            }
+            continue;
+        }
+        // ECM finds the whole of a prime power at once, so roots come first.
+        // Every prime factor of c is above TRIAL_BOUND.
+        let root = (2..=c.bits() as u32 / 16)
+            .map(|k| (c.nth_root(k), k))
+            .find(|(r, k)| r.pow(*k) == c);
+        let d = match root {
+            Some((r, _)) => r,
+            None => ecm_with_rng(&c, 2000, 150000, 20, rng)?,
+        };
+        if d.is_one() || d >= c || !(&c % &d).is_zero() {
+            return None;
+        }
+        cofactors.push(&c / &d);
+        cofactors.push(d);
+    }
+
+    factors.sort_by_key(|(cert, _)| cert.n());
+    Some(Certificate::Pocklington {
+        n: n.clone(),
+        factors,
+    })
+}
+
+fn pocklington_witness(n: &BigUint, q: &BigUint) -> Option<BigUint> {
+    let n1: BigUint = n - 1u32;
+    let e = &n1 / q;
+    (2..MAX_WITNESS)
+        .map(BigUint::from)
+        .find(|a| a.modpow(&n1, n).is_one() && !a.modpow(&e, n).is_one())
+}
+
+// Product of the full powers of the listed primes in n - 1.
+fn factored_part(n1: &BigUint, factors: &[(Certificate, BigUint)]) -> BigUint {
+    let mut f = BigUint::one();
+    for (cert, _) in factors.iter() {
+        let q = cert.n();
+        let mut m = n1.clone();
+        while !q.is_zero() && !q.is_one() && (&m % &q).is_zero() {
+            m /= &q;
+            f *= &q;
+        }
+    }
+    f
+}
+
+fn large_enough(n: &BigUint, f: &BigUint) -> bool {
+    f.pow(3) >= *n
+}
+
+pub fn verify_certificate(n: &BigUint, cert: &Certificate) -> bool {
+    if cert.n() != *n {
+        return false;
+    }
+    match cert {
+        Certificate::Small(p) => *p < SMALL_BOUND && is_prime_by_trial_division(*p),
+        Certificate::Pratt {
+            witness, factors, ..
+        } => {
+            let n1: BigUint = n - 1u32;
+            let a = BigUint::from(*witness);
+            let factors: Vec<_> = factors
+                .iter()
+                .map(|cert| (cert.clone(), a.clone()))
+                .collect();
+            n > &BigUint::one() && verify_factors(n, &factors) && factored_part(&n1, &factors) == n1
+        }
+        Certificate::Pocklington { factors, .. } => {
+            if n <= &BigUint::one() || !verify_factors(n, factors) {
+                return false;
+            }
+            let f = factored_part(&(n - 1u32), factors);
+            factored_part_suffices(n, &f)
+        }
+        Certificate::Ecpp {
+            a, b, m, x, y, q, ..
+        } => {
+            let qn = q.n();
+            verify_ecpp_step(n, a, b, m, (x, y), &qn) && verify_certificate(&qn, q)
+        }
+    }
+}
+
+// Whether every prime factor of n being 1 mod F, for F | n - 1, proves n prime.
+pub(crate) fn factored_part_suffices(n: &BigUint, f: &BigUint) -> bool {
+    if &(f * f) >= n {
+        return true;
+    }
+    if !large_enough(n, f) {
+        return false;
+    }
+    // Brillhart–Lehmer–Selfridge: with n = c2 F^2 + c1 F + 1, n is prime iff
+    // c1^2 - 4 c2 is not a square.
+    let (c2, c1) = ((n - 1u32) / f).div_rem(f);
+    let d = BigInt::from(&c1 * &c1) - BigInt::from(c2 * 4u32);
+    match d.to_biguint() {
+        Some(d) => &d.sqrt() * &d.sqrt() != d,
+        None => true,
+    }
+}
+
+// Primes strictly increasing, each dividing n - 1 and certified, with
+// a^(n-1) = 1 and gcd(a^((n-1)/q) - 1, n) = 1 for its witness a.
+fn verify_factors(n: &BigUint, factors: &[(Certificate, BigUint)]) -> bool {
+    let n1: BigUint = n - 1u32;
+    let qs: Vec<BigUint> = factors.iter().map(|(cert, _)| cert.n()).collect();
+    qs.windows(2).all(|w| w[0] < w[1])
+        && factors.iter().zip(qs.iter()).all(|((cert, a), q)| {
+            if q <= &BigUint::one() || !(&n1 % q).is_zero() || !verify_certificate(q, cert) {
+                return false;
+            }
+            let x = a.modpow(&(&n1 / q), n);
+            a.modpow(&n1, n).is_one() && ((x + &n1) % n).gcd(n).is_one()
+        })
+}
+
+fn is_prime_by_trial_division(p: u64) -> bool {
+    p >= 2
+        && (2..)
+            .take_while(|&d| d <= p / d)
+            .all(|d| !p.is_multiple_of(d))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn pratt() {
+        let p = (1u128 << 127) - 1;
+        let cert = pratt_certificate(p).unwrap();
+        assert!(verify_certificate(&p.into(), &cert));
+        assert!(!verify_certificate(&(p - 2).into(), &cert));
+        assert_eq!(pratt_certificate(p - 2), None);
+        assert_eq!(pratt_certificate(561), None);
+
+        let mut forged = cert.clone();
+        if let Certificate::Pratt { factors, .. } = &mut forged {
+            factors.pop();
+        }
+        assert!(!verify_certificate(&p.into(), &forged));
+        let mut forged = cert;
+        if let Certificate::Pratt { witness, .. } = &mut forged {
+            *witness = 1;
+        }
+        assert!(!verify_certificate(&p.into(), &forged));
+
+        assert!(!verify_certificate(
+            &561u32.into(),
+            &Certificate::Small(561)
+        ));
+        let p = 18446744073709551557u64;
+        assert!(!verify_certificate(&p.into(), &Certificate::Small(p)));
+    }
+
+    #[test]
+    fn pocklington() {
+        // n - 1 = 2^134 * 9 is fully factored.
+        let n: BigUint = "196002643346460554954903773880698489798657"
+            .parse()
+            .unwrap();
+        let cert = pocklington_certificate(&n).unwrap();
+        assert!(verify_certificate(&n, &cert));
+
+        // n - 1 = 2^93 * 23 * p * q with 85-bit p and q, so only the
+        // Brillhart–Lehmer–Selfridge bound applies.
+        let n: BigUint =
+            "170445955357329439663610528481280766788810976459265916758754984786751936650018817"
+                .parse()
+                .unwrap();
+        let cert = pocklington_certificate(&n).unwrap();
+        assert!(verify_certificate(&n, &cert));
+        assert_eq!(pocklington_certificate(&(&n + 2u32)), None);
+
+        // n - 1 = 2 * q^2 * r with q = 1048583, so ECM splits q out of q^2 r
+        // twice.
+        let n: BigUint = "2722295281566835575350367985082745147803".parse().unwrap();
+        let cert = pocklington_certificate(&n).unwrap();
+        assert!(verify_certificate(&n, &cert));
+
+        let mut forged = cert;
+        if let Certificate::Pocklington { factors, .. } = &mut forged {
+            factors[0].1 = BigUint::from(1u32);
+        }
+        assert!(!verify_certificate(&n, &forged));
+    }
+}
@@ -0,0 +1,188 @@
+use num_bigint::{BigUint, RandBigInt};
+use num_integer::Integer;
+use num_traits::One;
+use rand::Rng;
+
+use crate::{
+    mod_inverse,
+    pm1::{stage2, BigRing, STAGE2_D},
+    sieve::primes_up_to,
+};
+
+type Point = (BigUint, BigUint);
+
+struct Curve<'a> {
+    n: &'a BigUint,
+    a24: BigUint,
+}
+
+impl Curve<'_> {
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        if a >= b {
+            a - b
+        } else {
+            self.n - (b - a)
+        }
+    }
+
+    fn dbl(&self, (x, z): &Point) -> Point {
+        let n = self.n;
+        let t1 = (x + z) * (x + z) % n;
+        let s = self.sub(x, z);
+        let t2 = &s * &s % n;
+        let t3 = self.sub(&t1, &t2);
+        let x2 = &t1 * &t2 % n;
+        let z2 = &t3 * ((&t2 + &self.a24 * &t3) % n) % n;
+        (x2, z2)
+    }
+
+    // P + Q given P - Q.
+    fn add(&self, (xp, zp): &Point, (xq, zq): &Point, (xd, zd): &Point) -> Point {
+        let n = self.n;
+        let u = self.sub(xp, zp) * (xq + zq) % n;
+        let v = (xp + zp) * self.sub(xq, zq) % n;
+        let s = &u + &v;
+        let d = self.sub(&u, &v);
+        (zd * (&s * &s % n) % n, xd * (&d * &d % n) % n)
+    }
+
+    fn mul(&self, k: u64, p: &Point) -> Point {
+        let mut r0 = p.clone();
+        let mut r1 = self.dbl(p);
+        for i in (0..63 - k.leading_zeros()).rev() {
+            if k >> i & 1 == 1 {
+                r0 = self.add(&r1, &r0, p);
+                r1 = self.dbl(&r1);
+            } else {
+                r1 = self.add(&r0, &r1, p);
+                r0 = self.dbl(&r0);
+            }
+        }
+        r0
+    }
+}
+
+fn nontrivial_gcd(a: &BigUint, n: &BigUint) -> Option<BigUint> {
+    let g = a.gcd(n);
+    if !g.is_one() && &g != n {
+        Some(g)
+    } else {
+        None
+    }
+}
+
+// Builds a curve and starting point with Suyama's parametrisation. A failed
+// inversion of the curve coefficient may itself expose a factor of n.
+fn suyama_curve<'a>(
+    n: &'a BigUint,
+    sigma: &BigUint,
+) -> Result<(Curve<'a>, Point), Option<BigUint>> {
+    let u = (sigma * sigma + n - 5u32) % n;
+    let v = (sigma * 4u32) % n;
+    let x = u.modpow(&3u32.into(), n);
+    let z = v.modpow(&3u32.into(), n);
+
+    let vu = (&v + n - &u) % n;
+    let num = vu.modpow(&3u32.into(), n) * ((&u * 3u32 + &v) % n) % n;
+    let den = &x * &v * 16u32 % n;
+    let inv = mod_inverse(&den, n).map_err(|g| nontrivial_gcd(&g, n))?;
+    let a24 = num * inv % n;
+
+    Ok((Curve { n, a24 }, (x, z)))
+}
+
+pub fn ecm(n: &BigUint, b1: u64, b2: u64, curves: usize) -> Option<BigUint> {
+    ecm_with_rng(n, b1, b2, curves, &mut rand::thread_rng())
+}
+
+pub fn ecm_with_rng(
+    n: &BigUint,
+    b1: u64,
+    b2: u64,
+    curves: usize,
+    rng: &mut impl Rng,
+) -> Option<BigUint> {
+    if n <= &BigUint::from(3u32) {
+        return None;
+    }
+    if !n.bit(0) {
+        return Some(2u32.into());
+    }
+    // sigma is drawn from [6, n), and the only odd n below 7 left is prime.
+    if n < &BigUint::from(7u32) {
+        return None;
+    }
+
+    let primes = primes_up_to(b2.max(STAGE2_D));
+
+    for _ in 0..curves {
+        let sigma = rng.gen_biguint_range(&6u32.into(), n);
+        let (curve, mut q) = match suyama_curve(n, &sigma) {
+            Ok(c) => c,
+            Err(Some(g)) => return Some(g),
+            Err(None) => continue,
+        };
+
+        // Stage 1: multiply by every prime power up to B1, and by the primes
+        // below the first stage 2 window once.
+        for &p in primes.iter().take_while(|&&p| p <= b1.max(STAGE2_D)) {
+            let mut pk = p;
+            loop {
+                q = curve.mul(p, &q);
+                if p > b1 || pk > b1 / p {
+                    break;
+                }
+                pk *= p;
+            }
+        }
+
+        let g = q.1.gcd(n);
+        if &g == n {
+            continue;
+        }
+        if !g.is_one() {
+            return Some(g);
+        }
+
+        // Stage 2: kD Q = ±jQ exactly when their x coordinates agree.
+        let acc = stage2(
+            &BigRing(n),
+            &primes,
+            b1.max(STAGE2_D),
+            b2,
+            |k| curve.mul(k, &q),
+            |a, b, d| curve.add(a, b, d),
+            |(xr, zr), (xj, zj)| curve.sub(&(xr * zj % n), &(xj * zr % n)),
+        );
+        if let Some(g) = nontrivial_gcd(&acc, n) {
+            return Some(g);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn ecm_finds_factor() {
+        let p = BigUint::from(1000000007u64);
+        let q = BigUint::from(241393502644931236824083437316691947053_u128);
+        let n = &p * &q;
+        let f = ecm(&n, 2000, 200000, 200).unwrap();
+        assert!(f == p || f == q);
+
+        assert_eq!(ecm(&q, 2000, 200000, 5), None);
+        assert_eq!(ecm(&(&q * 2u32), 2000, 200000, 5), Some(2u32.into()));
+        for n in 0..7u32 {
+            let expected = if n >= 4 && n.is_multiple_of(2) {
+                Some(2u32.into())
+            } else {
+                None
+            };
+            assert_eq!(ecm(&n.into(), 2000, 200000, 5), expected);
+        }
+    }
+}
@@ -0,0 +1,492 @@
+// Atkin–Morain elliptic curve primality proving. Each step of the down-run
+// takes a discriminant D for which 4n = u^2 + |D| v^2, so that a curve with
+// complex multiplication by D has order n + 1 ± u (or a twist of it), and
+// looks for such an order m = k q with a probable prime q > (n^(1/4) + 1)^2.
+// The curve itself comes from a root of the Hilbert class polynomial of D.
+
+use std::convert::TryFrom;
+
+use num_bigint::{BigInt, BigUint, RandBigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::Rng;
+
+use crate::{
+    bpsw_test, ecm_with_rng, hilbert::HILBERT_CLASS_POLYNOMIALS, mod_inverse,
+    pratt_certificate_with_rng, primality::jacobi, sieve::primes_up_to, Certificate,
+};
+
+// The down-run ends with a Pratt certificate below this size.
+const PRATT_BITS: u64 = 64;
+
+// Small factors of candidate orders are removed up to this bound.
+const SMOOTH_BOUND: u64 = 1 << 20;
+
+// ECM effort spent on each candidate order when no cofactor is prime.
+const STRIP_B1: u64 = 2000;
+const STRIP_B2: u64 = 100_000;
+const STRIP_CURVES: usize = 8;
+
+const MAX_ATTEMPTS: usize = 100;
+
+pub fn ecpp_certificate(n: &BigUint) -> Option<Certificate> {
+    ecpp_certificate_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn ecpp_certificate_with_rng(n: &BigUint, rng: &mut impl Rng) -> Option<Certificate> {
+    down_run(n, &product(&primes_up_to(SMOOTH_BOUND)), rng)
+}
+
+fn product(xs: &[u64]) -> BigUint {
+    if xs.len() <= 16 {
+        return xs.iter().fold(BigUint::one(), |acc, &x| acc * x);
+    }
+    let (l, r) = xs.split_at(xs.len() / 2);
+    product(l) * product(r)
+}
+
+// m with every prime factor of the primorial divided out.
+fn remove_smooth(m: &BigUint, primorial: &BigUint) -> BigUint {
+    let mut q = m.clone();
+    let mut g = q.gcd(&(primorial % m));
+    while !g.is_one() {
+        q /= &g;
+        g = q.gcd(&g);
+    }
+    q
+}
+
+fn down_run(n: &BigUint, primorial: &BigUint, rng: &mut impl Rng) -> Option<Certificate> {
+    if n.bits() <= PRATT_BITS {
+        return pratt_certificate_with_rng(u128::try_from(n).ok()?, rng);
+    }
+    if n.is_even() || !bpsw_test(n) {
+        return None;
+    }
+
+    // Try the candidates with the smallest q first, backtracking if the run
+    // gets stuck further down. If that fails, go over the composite q again
+    // and take out medium-sized factors with ECM.
+    let candidates = candidate_orders(n, primorial);
+    for &strip in [false, true].iter() {
+        for (d, poly, m, q) in candidates.iter() {
+            let q = match (strip, bpsw_test(q)) {
+                (false, true) => q.clone(),
+                (true, false) => match strip_medium_factors(n, q.clone(), rng) {
+                    Some(q) => q,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let (curve, (x, y)) = match find_curve(n, *d, poly, m, &q, rng) {
+                Some(c) => c,
+                None => continue,
+            };
+            if let Some(next) = down_run(&q, primorial, rng) {
+                return Some(Certificate::Ecpp {
+                    n: n.clone(),
+                    a: curve.a,
+                    b: curve.b,
+                    m: m.clone(),
+                    x,
+                    y,
+                    q: Box::new(next),
+                });
+            }
+        }
+    }
+    None
+}
+
+fn strip_medium_factors(n: &BigUint, mut q: BigUint, rng: &mut impl Rng) -> Option<BigUint> {
+    while !bpsw_test(&q) {
+        q /= ecm_with_rng(&q, STRIP_B1, STRIP_B2, STRIP_CURVES, rng)?;
+        if !large_enough(&q, n) {
+            return None;
+        }
+    }
+    Some(q)
+}
+
+type Candidate = (i64, &'static [&'static str], BigUint, BigUint);
+
+fn candidate_orders(n: &BigUint, primorial: &BigUint) -> Vec<Candidate> {
+    let mut ret = vec![];
+    for &(d, poly) in HILBERT_CLASS_POLYNOMIALS.iter() {
+        let (u, v) = match cornacchia(d, n) {
+            Some(uv) => uv,
+            None => continue,
+        };
+        let traces = match d {
+            -3 => vec![
+                u.clone(),
+                (&u + &v * 3u32) >> 1,
+                (BigInt::from(u.clone()) - BigInt::from(&v * 3u32))
+                    .magnitude()
+                    .clone()
+                    >> 1,
+            ],
+            -4 => vec![u, v << 1],
+            _ => vec![u],
+        };
+        for t in traces {
+            for m in [n + 1u32 + &t, n + 1u32 - &t].iter() {
+                let q = remove_smooth(m, primorial);
+                if &q < n && large_enough(&q, n) {
+                    ret.push((d, poly, m.clone(), q));
+                }
+            }
+        }
+    }
+    ret.sort_by(|a, b| a.3.cmp(&b.3));
+    ret
+}
+
+// q > (n^(1/4) + 1)^2, checked conservatively with the integer square root.
+fn large_enough(q: &BigUint, n: &BigUint) -> bool {
+    let s = q.sqrt();
+    s > BigUint::one() && (s - 1u32).pow(4) > *n
+}
+
+// Solves 4n = u^2 + |d| v^2 for prime n.
+fn cornacchia(d: i64, n: &BigUint) -> Option<(BigUint, BigUint)> {
+    let dn = to_mod(&d.into(), n);
+    let mut x = sqrt_mod(&dn, n)?;
+    if x.bit(0) != (d & 1 == 1) {
+        x = n - x;
+    }
+    let n4: BigUint = n << 2;
+    let l = n4.sqrt();
+    let (mut a, mut b) = (n << 1, x);
+    while b > l {
+        let r = &a % &b;
+        a = std::mem::replace(&mut b, r);
+    }
+    let (c, r) = (n4 - &b * &b).div_rem(&BigUint::from(d.unsigned_abs()));
+    let v = c.sqrt();
+    if r.is_zero() && &v * &v == c {
+        Some((b, v))
+    } else {
+        None
+    }
+}
+
+fn to_mod(x: &BigInt, n: &BigUint) -> BigUint {
+    x.mod_floor(&BigInt::from_biguint(Sign::Plus, n.clone()))
+        .to_biguint()
+        .unwrap()
+}
+
+// Tonelli–Shanks for prime p.
+pub(crate) fn sqrt_mod(a: &BigUint, p: &BigUint) -> Option<BigUint> {
+    let a = a % p;
+    if a.is_zero() {
+        return Some(a);
+    }
+    if jacobi(&a, p) != 1 {
+        return None;
+    }
+    let p1: BigUint = p - 1u32;
+    let s = p1.trailing_zeros().unwrap();
+    let q = &p1 >> s;
+    let z = (2..MAX_ATTEMPTS as u32)
+        .map(BigUint::from)
+        .find(|z| jacobi(z, p) == -1)?;
+
+    let mut m = s;
+    let mut c = z.modpow(&q, p);
+    let mut t = a.modpow(&q, p);
+    let mut r = a.modpow(&((&q + 1u32) >> 1), p);
+    while !t.is_one() {
+        let mut i = 0;
+        let mut t2 = t.clone();
+        while !t2.is_one() {
+            t2 = &t2 * &t2 % p;
+            i += 1;
+            if i == m {
+                return None;
+            }
+        }
+        let b = c.modpow(&(BigUint::one() << (m - i - 1)), p);
+        m = i;
+        c = &b * &b % p;
+        t = t * &c % p;
+        r = r * b % p;
+    }
+    Some(r)
+}
+
+// y^2 = x^3 + ax + b over Z/nZ.
+struct Curve<'a> {
+    n: &'a BigUint,
+    a: BigUint,
+    b: BigUint,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Point {
+    Infinity,
+    Affine(BigUint, BigUint),
+}
+
+impl<'a> Curve<'a> {
+    fn new(n: &'a BigUint, a: BigUint, b: BigUint) -> Self {
+        Curve { n, a, b }
+    }
+
+    fn rhs(&self, x: &BigUint) -> BigUint {
+        ((x * x + &self.a) * x + &self.b) % self.n
+    }
+
+    fn is_nonsingular(&self) -> bool {
+        let d = self.a.pow(3) * 4u32 + &self.b * &self.b * 27u32;
+        d.gcd(self.n).is_one()
+    }
+
+    fn contains(&self, x: &BigUint, y: &BigUint) -> bool {
+        y * y % self.n == self.rhs(x)
+    }
+
+    // None when a denominator is a non-trivial zero divisor. Over a composite
+    // n every completed step is still correct modulo each prime factor.
+    fn add(&self, p: &Point, q: &Point) -> Option<Point> {
+        let n = self.n;
+        let (x1, y1, x2, y2) = match (p, q) {
+            (Point::Infinity, _) => return Some(q.clone()),
+            (_, Point::Infinity) => return Some(p.clone()),
+            (Point::Affine(x1, y1), Point::Affine(x2, y2)) => (x1, y1, x2, y2),
+        };
+        let (num, den) = if x1 == x2 {
+            if ((y1 + y2) % n).is_zero() {
+                return Some(Point::Infinity);
+            }
+            if y1 != y2 {
+                return None;
+            }
+            ((x1 * x1 * 3u32 + &self.a) % n, (y1 << 1) % n)
+        } else {
+            ((y2 + n - y1) % n, (x2 + n - x1) % n)
+        };
+        let l = num * mod_inverse(&den, n).ok()? % n;
+        let x3 = (&l * &l + (n << 1) - x1 - x2) % n;
+        let y3 = (l * ((x1 + n - &x3) % n) + n - y1) % n;
+        Some(Point::Affine(x3, y3))
+    }
+
+    fn mul(&self, p: &Point, k: &BigUint) -> Option<Point> {
+        let mut ret = Point::Infinity;
+        for i in (0..k.bits()).rev() {
+            ret = self.add(&ret, &ret)?;
+            if k.bit(i) {
+                ret = self.add(&ret, p)?;
+            }
+        }
+        Some(ret)
+    }
+
+    fn random_point(&self, rng: &mut impl Rng) -> Option<(BigUint, BigUint)> {
+        (0..MAX_ATTEMPTS).find_map(|_| {
+            let x = rng.gen_biguint_below(self.n);
+            let y = sqrt_mod(&self.rhs(&x), self.n)?;
+            Some((x, y))
+        })
+    }
+}
+
+// A curve of order m and a point whose multiple by m / q has order q.
+fn find_curve<'a>(
+    n: &'a BigUint,
+    d: i64,
+    poly: &[&str],
+    m: &BigUint,
+    q: &BigUint,
+    rng: &mut impl Rng,
+) -> Option<(Curve<'a>, (BigUint, BigUint))> {
+    let j = class_polynomial_root(poly, n, rng)?;
+    let k = m / q;
+
+    // Twists share j, so go through them until one has order m.
+    let curves: Vec<(BigUint, BigUint)> = match d {
+        -3 => (1..MAX_ATTEMPTS as u32)
+            .map(|b| (BigUint::zero(), b.into()))
+            .collect(),
+        -4 => (1..MAX_ATTEMPTS as u32)
+            .map(|a| (a.into(), BigUint::zero()))
+            .collect(),
+        _ => {
+            let c = (2..MAX_ATTEMPTS as u32)
+                .map(BigUint::from)
+                .find(|c| jacobi(c, n) == -1)?;
+            let t = &j * mod_inverse(&((BigUint::from(1728u32) + n - &j) % n), n).ok()? % n;
+            let (a, b) = (&t * 3u32 % n, &t * 2u32 % n);
+            let twist = (&a * &c * &c % n, &b * c.pow(3) % n);
+            vec![(a, b), twist]
+        }
+    };
+
+    for (a, b) in curves {
+        let curve = Curve::new(n, a, b);
+        if !curve.is_nonsingular() {
+            continue;
+        }
+        for _ in 0..4 {
+            let (x, y) = curve.random_point(rng)?;
+            let p = Point::Affine(x.clone(), y.clone());
+            if curve.mul(&p, m) != Some(Point::Infinity) {
+                break;
+            }
+            if let Some(r @ Point::Affine(..)) = curve.mul(&p, &k) {
+                if curve.mul(&r, q) == Some(Point::Infinity) {
+                    return Some((curve, (x, y)));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Polynomials over Z/nZ, lowest coefficient first, without trailing zeros.
+type Poly = Vec<BigUint>;
+
+fn trim(mut f: Poly) -> Poly {
+    while f.last().is_some_and(|c| c.is_zero()) {
+        f.pop();
+    }
+    f
+}
+
+fn poly_rem(a: &[BigUint], f: &[BigUint], n: &BigUint) -> Poly {
+    let mut a = a.to_vec();
+    let df = f.len() - 1;
+    let inv = mod_inverse(&f[df], n).unwrap();
+    while a.len() > df {
+        let c = a.pop().unwrap() * &inv % n;
+        let shift = a.len() - df;
+        for (i, fi) in f[..df].iter().enumerate() {
+            a[shift + i] = (&a[shift + i] + n - &c * fi % n) % n;
+        }
+    }
+    trim(a)
+}
+
+fn poly_mul_mod(a: &[BigUint], b: &[BigUint], f: &[BigUint], n: &BigUint) -> Poly {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut c = vec![BigUint::zero(); a.len() + b.len() - 1];
+    for (i, ai) in a.iter().enumerate() {
+        for (j, bj) in b.iter().enumerate() {
+            c[i + j] = (&c[i + j] + ai * bj) % n;
+        }
+    }
+    poly_rem(&c, f, n)
+}
+
+fn poly_pow_mod(a: &[BigUint], e: &BigUint, f: &[BigUint], n: &BigUint) -> Poly {
+    let mut ret = poly_rem(&[BigUint::one()], f, n);
+    for i in (0..e.bits()).rev() {
+        ret = poly_mul_mod(&ret, &ret, f, n);
+        if e.bit(i) {
+            ret = poly_mul_mod(&ret, a, f, n);
+        }
+    }
+    ret
+}
+
+fn poly_gcd(a: &[BigUint], b: &[BigUint], n: &BigUint) -> Poly {
+    let (mut a, mut b) = (trim(a.to_vec()), trim(b.to_vec()));
+    while !b.is_empty() {
+        let r = poly_rem(&a, &b, n);
+        a = std::mem::replace(&mut b, r);
+    }
+    a
+}
+
+fn poly_sub(a: &[BigUint], b: &[BigUint], n: &BigUint) -> Poly {
+    let mut c = a.to_vec();
+    c.resize(a.len().max(b.len()), BigUint::zero());
+    for (ci, bi) in c.iter_mut().zip(b.iter()) {
+        *ci = (&*ci + n - bi) % n;
+    }
+    trim(c)
+}
+
+// Some root of the class polynomial modulo prime n, by Cantor–Zassenhaus.
+fn class_polynomial_root(poly: &[&str], n: &BigUint, rng: &mut impl Rng) -> Option<BigUint> {
+    let mut f: Poly = poly
+        .iter()
+        .map(|c| to_mod(&c.parse().unwrap(), n))
+        .collect();
+    f.push(BigUint::one());
+
+    // The roots are exactly the linear factors of gcd(f, x^n - x).
+    let x = vec![BigUint::zero(), BigUint::one()];
+    let xn = poly_pow_mod(&x, n, &f, n);
+    let mut g = poly_gcd(&f, &poly_sub(&xn, &x, n), n);
+
+    let e: BigUint = (n - 1u32) >> 1;
+    for _ in 0..MAX_ATTEMPTS {
+        if g.len() <= 2 {
+            break;
+        }
+        let delta = rng.gen_biguint_below(n);
+        let y = poly_pow_mod(&[delta, BigUint::one()], &e, &g, n);
+        let h = poly_gcd(&g, &poly_sub(&y, &[BigUint::one()], n), n);
+        if h.len() > 1 && h.len() < g.len() {
+            g = h;
+        }
+    }
+    if g.len() != 2 {
+        return None;
+    }
+    let inv = mod_inverse(&g[1], n).ok()?;
+    Some((n - &g[0] * inv % n) % n)
+}
+
+pub(crate) fn verify_ecpp_step(
+    n: &BigUint,
+    a: &BigUint,
+    b: &BigUint,
+    m: &BigUint,
+    (x, y): (&BigUint, &BigUint),
+    q: &BigUint,
+) -> bool {
+    if n.bits() <= 2 || !n.gcd(&6u32.into()).is_one() || q.is_zero() || !(m % q).is_zero() {
+        return false;
+    }
+    if !large_enough(q, n) {
+        return false;
+    }
+    let curve = Curve::new(n, a % n, b % n);
+    if !curve.is_nonsingular() || !curve.contains(&(x % n), &(y % n)) {
+        return false;
+    }
+    let p = Point::Affine(x % n, y % n);
+    match curve.mul(&p, &(m / q)) {
+        Some(r @ Point::Affine(..)) => curve.mul(&r, q) == Some(Point::Infinity),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn ecpp() {
+        let n: BigUint = "1461501637330902918203684832716283019655932542983"
+            .parse()
+            .unwrap();
+        let cert = ecpp_certificate(&n).unwrap();
+        assert!(matches!(cert, Certificate::Ecpp { .. }));
+        assert!(verify_certificate(&n, &cert));
+        assert_eq!(ecpp_certificate(&(&n + 2u32)), None);
+
+        let mut forged = cert;
+        if let Certificate::Ecpp { x, .. } = &mut forged {
+            *x += 1u32;
+        }
+        assert!(!verify_certificate(&n, &forged));
+    }
+}
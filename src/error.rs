@@ -0,0 +1,24 @@
+use std::error::Error;
+use std::fmt;
+
+use num_bigint::BigUint;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FactorError {
+    Zero,
+    // A splitting routine returned something that is not a proper divisor of n.
+    BadSplit { n: BigUint, factor: BigUint },
+}
+
+impl fmt::Display for FactorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FactorError::Zero => write!(f, "cannot factorize 0"),
+            FactorError::BadSplit { n, factor } => {
+                write!(f, "{} is not a proper divisor of {}", factor, n)
+            }
+        }
+    }
+}
+
+impl Error for FactorError {}
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::ops::Mul;
+
+use rand::Rng;
+
+use crate::{try_factorization_with_rng, FactorError};
+
+#[derive(Clone, PartialEq, Eq, Debug, Default, Hash)]
+pub struct Factorization {
+    // Sorted by prime, exponents are never zero.
+    factors: Vec<(u128, u32)>,
+}
+
+pub fn factorize(n: u128) -> Factorization {
+    factorize_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn factorize_with_rng(n: u128, rng: &mut impl Rng) -> Factorization {
+    match try_factorize_with_rng(n, rng) {
+        Ok(ret) => ret,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_factorize(n: u128) -> Result<Factorization, FactorError> {
+    try_factorize_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn try_factorize_with_rng(n: u128, rng: &mut impl Rng) -> Result<Factorization, FactorError> {
+    try_factorization_with_rng(n, rng).map(Factorization::from_primes)
+}
+
+impl Factorization {
+    pub fn one() -> Self {
+        Factorization::default()
+    }
+
+    pub fn from_primes(primes: impl IntoIterator<Item = u128>) -> Self {
+        let mut primes: Vec<u128> = primes.into_iter().collect();
+        primes.sort_unstable();
+        let mut factors: Vec<(u128, u32)> = vec![];
+        for p in primes {
+            match factors.last_mut() {
+                Some((q, e)) if *q == p => *e += 1,
+                _ => factors.push((p, 1)),
+            }
+        }
+        Factorization { factors }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u128, u32)> + '_ {
+        self.factors.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.factors.len()
+    }
+
+    pub fn exponent_of(&self, p: u128) -> u32 {
+        match self.factors.binary_search_by_key(&p, |&(q, _)| q) {
+            Ok(i) => self.factors[i].1,
+            Err(_) => 0,
+        }
+    }
+
+    pub fn is_square_free(&self) -> bool {
+        self.factors.iter().all(|&(_, e)| e == 1)
+    }
+
+    pub fn radical(&self) -> Factorization {
+        Factorization {
+            factors: self.factors.iter().map(|&(p, _)| (p, 1)).collect(),
+        }
+    }
+
+    // None if the value does not fit in u128.
+    pub fn value(&self) -> Option<u128> {
+        self.factors.iter().try_fold(1u128, |acc, &(p, e)| {
+            p.checked_pow(e).and_then(|pe| acc.checked_mul(pe))
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u128> {
+        self.factors
+            .iter()
+            .flat_map(|&(p, e)| std::iter::repeat_n(p, e as usize))
+            .collect()
+    }
+
+    fn merge(&self, other: &Factorization, f: impl Fn(u32, u32) -> u32) -> Factorization {
+        let mut factors = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.factors.len() || j < other.factors.len() {
+            let a = self.factors.get(i).copied();
+            let b = other.factors.get(j).copied();
+            let (p, e) = match (a, b) {
+                (Some((p, e)), Some((q, _))) if p < q => {
+                    i += 1;
+                    (p, f(e, 0))
+                }
+                (Some((p, _)), Some((q, e))) if q < p => {
+                    j += 1;
+                    (q, f(0, e))
+                }
+                (Some((p, e1)), Some((_, e2))) => {
+                    i += 1;
+                    j += 1;
+                    (p, f(e1, e2))
+                }
+                (Some((p, e)), None) => {
+                    i += 1;
+                    (p, f(e, 0))
+                }
+                (None, Some((q, e))) => {
+                    j += 1;
+                    (q, f(0, e))
+                }
+                (None, None) => unreachable!(),
+            };
+            if e > 0 {
+                factors.push((p, e));
+            }
+        }
+        Factorization { factors }
+    }
+
+    pub fn gcd(&self, other: &Factorization) -> Factorization {
+        self.merge(other, u32::min)
+    }
+
+    pub fn lcm(&self, other: &Factorization) -> Factorization {
+        self.merge(other, u32::max)
+    }
+}
+
+impl Mul for &Factorization {
+    type Output = Factorization;
+
+    fn mul(self, other: &Factorization) -> Factorization {
+        self.merge(other, |a, b| a + b)
+    }
+}
+
+impl Mul for Factorization {
+    type Output = Factorization;
+
+    fn mul(self, other: Factorization) -> Factorization {
+        &self * &other
+    }
+}
+
+impl fmt::Display for Factorization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.factors.is_empty() {
+            return write!(f, "1");
+        }
+        for (i, &(p, e)) in self.factors.iter().enumerate() {
+            if i > 0 {
+                write!(f, " * ")?;
+            }
+            if e == 1 {
+                write!(f, "{}", p)?;
+            } else {
+                write!(f, "{}^{}", p, e)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn factorize_test() {
+        let f = factorize(360);
+        assert_eq!(f.iter().collect::<Vec<_>>(), [(2, 3), (3, 2), (5, 1)]);
+        assert_eq!(f.exponent_of(2), 3);
+        assert_eq!(f.exponent_of(7), 0);
+        assert_eq!(f.value(), Some(360));
+        assert_eq!(f.to_vec(), factorization(360));
+        assert!(!f.is_square_free());
+        assert_eq!(f.radical().value(), Some(30));
+        assert!(factorize(30).is_square_free());
+        assert_eq!(f.to_string(), "2^3 * 3^2 * 5");
+        assert_eq!(Factorization::one().value(), Some(1));
+
+        for x in 2..500u128 {
+            assert_eq!(factorize(x).value(), Some(x));
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = factorize(360);
+        let b = factorize(1050);
+        assert_eq!((&a * &b).value(), Some(360 * 1050));
+        assert_eq!(a.gcd(&b).value(), Some(30));
+        assert_eq!(a.lcm(&b).value(), Some(12600));
+        assert_eq!(a.gcd(&factorize(7)), Factorization::one());
+
+        let big = factorize(u128::MAX);
+        assert_eq!((&big * &big).value(), None);
+    }
+}
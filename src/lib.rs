@@ -1,23 +1,115 @@
-use num_bigint::{BigUint, RandBigInt};
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+
+use num_bigint::{BigInt, BigUint, RandBigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
 use rand::Rng;
 
-uint::construct_uint! {
-    struct U256(4);
+#[allow(clippy::manual_range_contains, clippy::assign_op_pattern)]
+mod u256 {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
 }
 
+use u256::U256;
+
+mod arith;
+mod audit;
+mod batch_gcd;
+mod cert;
+mod ecm;
+mod ecpp;
+mod error;
+mod factors;
+mod hilbert;
+mod montgomery;
+mod pm1;
+mod primality;
+mod prime_gen;
+mod prime_pi;
+mod provable;
+mod rsa;
+mod sha256;
+mod sieve;
+mod siqs;
+mod spf;
+
+pub use arith::{
+    carmichael_lambda, carmichael_lambda_biguint, carmichael_lambda_range, divisor_count,
+    divisor_count_biguint, divisor_count_range, divisor_sigma, divisor_sigma_biguint,
+    divisor_sigma_range, euler_phi, euler_phi_biguint, euler_phi_range, liouville,
+    liouville_biguint, liouville_range, moebius, moebius_biguint, moebius_range, radical,
+    radical_biguint, radical_range,
+};
+pub use audit::{audit_moduli, fermat_factor, AuditFinding, AuditOptions, Weakness};
+pub use batch_gcd::batch_gcd;
+pub use cert::{
+    pocklington_certificate, pocklington_certificate_with_rng, pratt_certificate,
+    pratt_certificate_with_rng, verify_certificate, Certificate,
+};
+pub use ecm::{ecm, ecm_with_rng};
+pub use ecpp::{ecpp_certificate, ecpp_certificate_with_rng};
+pub use error::FactorError;
+pub use factors::{
+    factorize, factorize_with_rng, try_factorize, try_factorize_with_rng, Factorization,
+};
+pub use montgomery::{Montgomery128, Montgomery64};
+pub use pm1::{
+    pollard_p_minus_1, pollard_p_minus_1_biguint, williams_p_plus_1, williams_p_plus_1_biguint,
+};
+pub use primality::{bpsw_test, is_prime_u128, is_prime_u64, is_strong_probable_prime};
+pub use prime_gen::{
+    gen_prime_exact_bits, gen_prime_in_range, gen_safe_prime, gen_sophie_germain_prime,
+    is_safe_prime, is_safe_prime_with_rng, next_prime, next_prime_u64, next_prime_with_rng,
+    prev_prime, prev_prime_u64, prev_prime_with_rng, TopBits,
+};
+pub use prime_pi::{nth_prime, prime_pi};
+pub use provable::{gen_maurer_prime, shawe_taylor_prime};
+pub use rsa::{gen_rsa_key, gen_rsa_key_with, RsaOptions, RsaPrivateKey};
+pub use sieve::{primes, primes_in_range, Primes};
+pub use siqs::{siqs, siqs_with_rng};
+pub use spf::{factor_range, factorize_with_table, FactorRange, SpfTable};
+
 pub fn factorization(n: u128) -> Vec<u128> {
-    if is_prime(&n.into()) {
-        return vec![n];
+    factorization_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn factorization_with_rng(n: u128, rng: &mut impl Rng) -> Vec<u128> {
+    match try_factorization_with_rng(n, rng) {
+        Ok(ret) => ret,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_factorization(n: u128) -> Result<Vec<u128>, FactorError> {
+    try_factorization_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn try_factorization_with_rng(n: u128, rng: &mut impl Rng) -> Result<Vec<u128>, FactorError> {
+    if n == 0 {
+        return Err(FactorError::Zero);
+    }
+    if n == 1 {
+        return Ok(vec![]);
+    }
+    if is_prime_u128(n) {
+        return Ok(vec![n]);
     }
 
     let mut ret = vec![];
     let mut n = n;
 
-    while !is_prime(&n.into()) {
-        let factor = pollard_rho(n);
-        assert!(n > factor);
-        assert!(n % factor == 0);
-        ret.append(&mut factorization(factor));
+    while !is_prime_u128(n) {
+        let factor = find_factor(n, rng);
+        if factor <= 1 || factor >= n || !n.is_multiple_of(factor) {
+            return Err(FactorError::BadSplit {
+                n: n.into(),
+                factor: factor.into(),
+            });
+        }
+        ret.append(&mut try_factorization_with_rng(factor, rng)?);
         n /= factor;
     }
 
@@ -26,78 +118,345 @@ pub fn factorization(n: u128) -> Vec<u128> {
     }
 
     ret.sort();
-    ret
+    Ok(ret)
+}
+
+pub fn factorize_biguint(n: &BigUint) -> Vec<BigUint> {
+    factorize_biguint_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn factorize_biguint_with_rng(n: &BigUint, rng: &mut impl Rng) -> Vec<BigUint> {
+    match try_factorize_biguint_with_rng(n, rng) {
+        Ok(ret) => ret,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn try_factorize_biguint(n: &BigUint) -> Result<Vec<BigUint>, FactorError> {
+    try_factorize_biguint_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn try_factorize_biguint_with_rng(
+    n: &BigUint,
+    rng: &mut impl Rng,
+) -> Result<Vec<BigUint>, FactorError> {
+    if let Ok(n) = u128::try_from(n) {
+        return Ok(try_factorization_with_rng(n, rng)?
+            .into_iter()
+            .map(BigUint::from)
+            .collect());
+    }
+
+    if is_prime_with_rng(n, rng) {
+        return Ok(vec![n.clone()]);
+    }
+
+    let factor = find_factor_biguint(n, rng);
+    if factor <= BigUint::one() || &factor >= n || !(n % &factor).is_zero() {
+        return Err(FactorError::BadSplit {
+            n: n.clone(),
+            factor,
+        });
+    }
+
+    let mut ret = try_factorize_biguint_with_rng(&factor, rng)?;
+    ret.append(&mut try_factorize_biguint_with_rng(&(n / &factor), rng)?);
+    ret.sort();
+    Ok(ret)
+}
+
+// Above this size rho is only given a bounded run, and SIQS takes over for
+// cofactors without small factors.
+const SIQS_MIN_BITS: u64 = 100;
+const SIQS_MAX_BITS: u64 = 332;
+
+const TRIAL_DIVISION_BOUND: u64 = 1 << 16;
+
+// p - 1 and p + 1 pre-passes run on anything rho would need more than a
+// few thousand steps for.
+const SMOOTH_PREPASS_MIN_BITS: u64 = 64;
+const SMOOTH_PREPASS_B1: u64 = 10_000;
+const SMOOTH_PREPASS_B2: u64 = 500_000;
+const SMOOTH_PREPASS_RHO_LIMIT: u64 = 1 << 10;
+
+// Primes up to SMOOTH_PREPASS_B2, sieved once and shared by every pre-pass.
+fn smooth_prepass_primes() -> &'static [u64] {
+    static PRIMES: OnceLock<Vec<u64>> = OnceLock::new();
+    PRIMES.get_or_init(|| sieve::primes_up_to(SMOOTH_PREPASS_B2))
+}
+
+fn trial_division_primes() -> &'static [u64] {
+    static PRIMES: OnceLock<Vec<u64>> = OnceLock::new();
+    PRIMES.get_or_init(|| sieve::primes_up_to(TRIAL_DIVISION_BOUND))
+}
+
+fn find_factor(n: u128, rng: &mut impl Rng) -> u128 {
+    if n < 1 << SMOOTH_PREPASS_MIN_BITS {
+        return pollard_rho_with_rng(n, rng);
+    }
+    // Small factors are cheaper to find with a short rho run.
+    if let Some(f) = pollard_rho_bounded(n, SMOOTH_PREPASS_RHO_LIMIT, rng) {
+        return f;
+    }
+    // Rho takes about n^(1/4) steps, so smaller inputs get a cheaper pre-pass.
+    let b1 = SMOOTH_PREPASS_B1.min((1 << ((128 - n.leading_zeros()) / 4)) / 100);
+    let b2 = SMOOTH_PREPASS_B2 / SMOOTH_PREPASS_B1 * b1;
+    if let Some(f) = pm1::smooth_prepass(n, smooth_prepass_primes(), b1, b2) {
+        return f;
+    }
+    if n < 1 << SIQS_MIN_BITS {
+        return pollard_rho_with_rng(n, rng);
+    }
+    if let Some(f) = pollard_rho_bounded(n, 1 << 20, rng) {
+        return f;
+    }
+    siqs_with_rng(&n.into(), rng)
+        .and_then(|f| u128::try_from(f).ok())
+        .unwrap_or_else(|| pollard_rho_with_rng(n, rng))
+}
+
+fn find_factor_biguint(n: &BigUint, rng: &mut impl Rng) -> BigUint {
+    for &p in trial_division_primes() {
+        if (n % p).is_zero() {
+            return p.into();
+        }
+    }
+    for k in 2..=n.bits() as u32 / 16 {
+        let r = n.nth_root(k);
+        if &r.pow(k) == n {
+            return r;
+        }
+    }
+    if let Some(f) = pm1::smooth_prepass_biguint(
+        n,
+        smooth_prepass_primes(),
+        SMOOTH_PREPASS_B1,
+        SMOOTH_PREPASS_B2,
+    ) {
+        return f;
+    }
+    if let Some(f) = ecm_with_rng(n, 2000, 150000, 20, rng) {
+        return f;
+    }
+    if n.bits() <= SIQS_MAX_BITS {
+        if let Some(f) = siqs_with_rng(n, rng) {
+            return f;
+        }
+    }
+    pollard_rho_biguint_with_rng(n, rng)
 }
 
 pub fn is_prime(n: &BigUint) -> bool {
-    if n == &0u32.into() || n == &1u32.into() {
-        false
-    } else if n == &2u32.into() || n == &3u32.into() {
-        true
-    } else {
-        miller_rabin_test(n, 100) == MillerRabinResult::ProbablyPrime
+    is_prime_with_rng(n, &mut rand::thread_rng())
+}
+
+// The input may be built to fool Miller–Rabin, so only the worst-case 4^-t
+// bound holds.
+pub fn is_prime_with_rng(n: &BigUint, rng: &mut impl Rng) -> bool {
+    PrimalityTest::MillerRabin(50).check(n, rng)
+}
+
+// For random candidates, which the average-case bound covers.
+pub(crate) fn is_random_prime_candidate(n: &BigUint, rng: &mut impl Rng) -> bool {
+    PrimalityTest::MillerRabin(miller_rabin_rounds(n.bits(), 100)).check(n, rng)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PrimalityTest {
+    MillerRabin(usize),
+    Bpsw,
+}
+
+impl PrimalityTest {
+    pub fn check(self, n: &BigUint, rng: &mut impl Rng) -> bool {
+        match self {
+            PrimalityTest::MillerRabin(k) => {
+                miller_rabin_test(n, k, rng) == MillerRabinResult::ProbablyPrime
+            }
+            PrimalityTest::Bpsw => bpsw_test(n),
+        }
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
-enum MillerRabinResult {
+pub fn is_prime_with(n: &BigUint, test: PrimalityTest) -> bool {
+    test.check(n, &mut rand::thread_rng())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MillerRabinResult {
     Composite,
     ProbablyPrime,
 }
 
-fn miller_rabin_test(n: &BigUint, k: usize) -> MillerRabinResult {
+pub fn miller_rabin_test(n: &BigUint, k: usize, rng: &mut impl Rng) -> MillerRabinResult {
+    // Bases are drawn from [2, n - 1), which is empty below 4.
+    if n < &BigUint::from(4u32) || n.is_even() {
+        return if n == &2u32.into() || n == &3u32.into() {
+            MillerRabinResult::ProbablyPrime
+        } else {
+            MillerRabinResult::Composite
+        };
+    }
     let t: BigUint = n - 1_u32;
-    let r = t.trailing_zeros().unwrap();
-    let d = t.clone() >> r;
-    let one = BigUint::from(1u32);
     let two = BigUint::from(2u32);
 
-    let mut rng = rand::thread_rng();
-    'outer: for _ in 0..k {
+    for _ in 0..k {
         let a: BigUint = rng.gen_biguint_range(&two, &t);
-        let mut x = a.modpow(&d, &n);
-
-        if x == one || x == t {
-            continue;
+        if !is_strong_probable_prime(n, &a) {
+            return MillerRabinResult::Composite;
         }
-        for _ in 1..r {
-            x = x.modpow(&two, &n);
-            if x == t {
-                continue 'outer;
-            }
-        }
-        return MillerRabinResult::Composite;
     }
     MillerRabinResult::ProbablyPrime
 }
 
+// Minimum number of Miller–Rabin rounds with random bases so that a random
+// odd `bits`-bit candidate passing all of them is composite with probability
+// at most 2^-error_log2, using the Damgård–Landrock–Pomerance bounds behind
+// the FIPS 186-5 tables and falling back to the worst-case 4^-t.
+pub fn miller_rabin_rounds(bits: u64, error_log2: u32) -> usize {
+    let target = -(error_log2 as f64);
+    (1..)
+        .find(|&t| dlp_error_log2(bits, t).min(-2.0 * t as f64) <= target)
+        .unwrap()
+}
+
+fn dlp_error_log2(k: u64, t: usize) -> f64 {
+    if k < 21 {
+        return 0.0;
+    }
+    let (kf, tf) = (k as f64, t as f64);
+    let mut p = f64::INFINITY;
+    if t == 1 {
+        p = p.min(kf * kf * 4f64.powf(2.0 - kf.sqrt()));
+    }
+    if (t == 2 && k >= 88) || (3.0 <= tf && tf <= kf / 9.0) {
+        p = p.min(kf.powf(1.5) * tf.exp2() / tf.sqrt() * 4f64.powf(2.0 - (tf * kf).sqrt()));
+    }
+    if kf / 9.0 <= tf && tf <= kf / 4.0 {
+        p = p.min(
+            0.35 * kf * (-5.0 * tf).exp2()
+                + kf.powf(3.75) / 7.0 * (-kf / 2.0 - 2.0 * tf).exp2()
+                + 12.0 * kf * (-kf / 4.0 - 3.0 * tf).exp2(),
+        );
+    }
+    if tf >= kf / 4.0 {
+        p = p.min(kf.powf(3.75) / 7.0 * (-kf / 2.0 - 2.0 * tf).exp2());
+    }
+    p.log2().min(0.0)
+}
+
 pub fn gen_prime(bits: usize, rng: &mut impl Rng) -> BigUint {
     loop {
         let n = rng.gen_biguint(bits as _);
-        if is_prime(&n) {
+        if is_random_prime_candidate(&n, rng) {
             break n;
         }
     }
 }
 
 pub fn pollard_rho(n: u128) -> u128 {
+    pollard_rho_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn pollard_rho_with_rng(n: u128, rng: &mut impl Rng) -> u128 {
+    if n.is_multiple_of(2) {
+        return 2;
+    }
     loop {
-        if let Some(ret) = pollard_rho_once(n, rand::thread_rng().gen_range(2, n)) {
+        let (x, c) = (rng.gen_range(2, n), rng.gen_range(1, n));
+        if let Some(ret) = pollard_rho_once(n, x, c, u64::MAX) {
             return ret;
         }
     }
 }
 
-fn pollard_rho_once(n: u128, mut x: u128) -> Option<u128> {
-    for cycle in 1.. {
-        let y = x;
-        for _ in 0..1 << cycle {
-            x = mul_mod(x, x, n);
-            x = (x + 1) % n;
-            let d = if x >= y { x - y } else { y - x };
-            let factor = gcd(d, n);
+// Gives up once Brent's cycle length exceeds `limit`, which makes factors
+// much above limit^2 unlikely to be found.
+fn pollard_rho_bounded(n: u128, limit: u64, rng: &mut impl Rng) -> Option<u128> {
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    (0..4).find_map(|_| pollard_rho_once(n, rng.gen_range(2, n), rng.gen_range(1, n), limit))
+}
+
+const RHO_BLOCK: u64 = 128;
+
+// Brent's variant: iterate x -> x^2 + c, multiplying |x - y| over blocks of
+// RHO_BLOCK steps and taking one gcd per block. If the batched gcd collapses
+// to n, the last block is replayed one step at a time.
+fn pollard_rho_once(n: u128, x: u128, c: u128, limit: u64) -> Option<u128> {
+    let m = Montgomery128::new(n);
+    let c = m.to_montgomery(c);
+    let f = |x| m.add(m.mul(x, x), c);
+
+    let mut y = m.to_montgomery(x);
+    let mut x = y;
+    let mut ys = y;
+    let mut q = m.one();
+    let mut factor = 1;
+    let mut r = 1;
+
+    while factor == 1 {
+        if r > limit {
+            return None;
+        }
+        x = y;
+        for _ in 0..r {
+            y = f(y);
+        }
+        let mut k = 0;
+        while k < r && factor == 1 {
+            ys = y;
+            for _ in 0..RHO_BLOCK.min(r - k) {
+                y = f(y);
+                q = m.mul(q, x.abs_diff(y));
+            }
+            factor = gcd(q, n);
+            k += RHO_BLOCK;
+        }
+        r *= 2;
+    }
+
+    if factor == n {
+        loop {
+            ys = f(ys);
+            factor = gcd(x.abs_diff(ys), n);
             if factor > 1 {
-                return if factor != n { Some(factor) } else { None };
+                break;
+            }
+        }
+    }
+
+    if factor != n {
+        Some(factor)
+    } else {
+        None
+    }
+}
+
+pub fn pollard_rho_biguint(n: &BigUint) -> BigUint {
+    pollard_rho_biguint_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn pollard_rho_biguint_with_rng(n: &BigUint, rng: &mut impl Rng) -> BigUint {
+    let two = BigUint::from(2u32);
+    loop {
+        let x = rng.gen_biguint_range(&two, n);
+        if let Some(ret) = pollard_rho_once_biguint(n, x) {
+            return ret;
+        }
+    }
+}
+
+fn pollard_rho_once_biguint(n: &BigUint, mut x: BigUint) -> Option<BigUint> {
+    for cycle in 1.. {
+        let y = x.clone();
+        for _ in 0..1u64 << cycle {
+            x = (&x * &x + 1u32) % n;
+            let d = if x >= y { &x - &y } else { &y - &x };
+            let factor = d.gcd(n);
+            if !factor.is_one() {
+                return if &factor != n { Some(factor) } else { None };
             }
         }
     }
@@ -112,7 +471,18 @@ fn gcd(a: u128, b: u128) -> u128 {
     }
 }
 
-fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+// Inverse of a modulo n, or the non-trivial gcd(a, n) when it does not exist.
+pub(crate) fn mod_inverse(a: &BigUint, n: &BigUint) -> Result<BigUint, BigUint> {
+    let a = BigInt::from_biguint(Sign::Plus, a % n);
+    let m = BigInt::from_biguint(Sign::Plus, n.clone());
+    let e = a.extended_gcd(&m);
+    if !e.gcd.is_one() {
+        return Err(e.gcd.to_biguint().unwrap());
+    }
+    Ok(e.x.mod_floor(&m).to_biguint().unwrap())
+}
+
+pub(crate) fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
     (U256::from(a) * U256::from(b) % U256::from(m)).as_u128()
 }
 
@@ -120,17 +490,29 @@ fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
 mod tests {
     use crate::*;
     use rand::Rng;
+    use std::convert::TryInto;
     use MillerRabinResult::*;
 
     #[test]
     fn miller_rabin() {
-        assert_eq!(miller_rabin_test(&4u32.into(), 100), Composite);
-        assert_eq!(miller_rabin_test(&5u32.into(), 100), ProbablyPrime);
-        assert_eq!(miller_rabin_test(&6u32.into(), 100), Composite);
-        assert_eq!(miller_rabin_test(&7u32.into(), 100), ProbablyPrime);
-        assert_eq!(miller_rabin_test(&8u32.into(), 100), Composite);
-        assert_eq!(miller_rabin_test(&9u32.into(), 100), Composite);
-        assert_eq!(miller_rabin_test(&10u32.into(), 100), Composite);
+        let mut rng = rand::thread_rng();
+        for n in 0..4u32 {
+            let expected = if n >= 2 { ProbablyPrime } else { Composite };
+            assert_eq!(miller_rabin_test(&n.into(), 100, &mut rng), expected);
+        }
+        assert_eq!(miller_rabin_test(&4u32.into(), 100, &mut rng), Composite);
+        assert_eq!(
+            miller_rabin_test(&5u32.into(), 100, &mut rng),
+            ProbablyPrime
+        );
+        assert_eq!(miller_rabin_test(&6u32.into(), 100, &mut rng), Composite);
+        assert_eq!(
+            miller_rabin_test(&7u32.into(), 100, &mut rng),
+            ProbablyPrime
+        );
+        assert_eq!(miller_rabin_test(&8u32.into(), 100, &mut rng), Composite);
+        assert_eq!(miller_rabin_test(&9u32.into(), 100, &mut rng), Composite);
+        assert_eq!(miller_rabin_test(&10u32.into(), 100, &mut rng), Composite);
 
         let primes = [
             241393502644931236824083437316691947053_u128,
@@ -144,7 +526,7 @@ mod tests {
         ];
 
         for &p in primes.iter() {
-            assert_eq!(miller_rabin_test(&p.into(), 100), ProbablyPrime);
+            assert_eq!(miller_rabin_test(&p.into(), 100, &mut rng), ProbablyPrime);
         }
 
         let composites = [
@@ -159,10 +541,48 @@ mod tests {
         ];
 
         for &c in composites.iter() {
-            assert_eq!(miller_rabin_test(&c.into(), 100), Composite);
+            assert_eq!(miller_rabin_test(&c.into(), 100, &mut rng), Composite);
         }
     }
 
+    #[test]
+    fn strong_probable_prime() {
+        // 2047 = 23 * 89 is the smallest strong pseudoprime to base 2.
+        assert!(is_strong_probable_prime(&2047u32.into(), &2u32.into()));
+        assert!(!is_strong_probable_prime(&2047u32.into(), &3u32.into()));
+        assert!(is_strong_probable_prime(&2u32.into(), &5u32.into()));
+        assert!(!is_strong_probable_prime(&1u32.into(), &2u32.into()));
+        assert!(!is_strong_probable_prime(&10u32.into(), &3u32.into()));
+        assert!(!is_strong_probable_prime(&7u32.into(), &14u32.into()));
+        assert!(!is_strong_probable_prime(&9u32.into(), &9u32.into()));
+        assert!(!is_strong_probable_prime(&9u32.into(), &18u32.into()));
+        assert!(!is_strong_probable_prime(&2u32.into(), &4u32.into()));
+    }
+
+    #[test]
+    fn miller_rabin_round_counts() {
+        // Handbook of Applied Cryptography, table 4.4 (error 2^-80).
+        let hac = [
+            (100, 27),
+            (150, 18),
+            (200, 15),
+            (250, 12),
+            (300, 9),
+            (350, 8),
+            (400, 7),
+            (450, 6),
+            (550, 5),
+            (650, 4),
+            (850, 3),
+            (1300, 2),
+        ];
+        for &(bits, rounds) in hac.iter() {
+            assert_eq!(miller_rabin_rounds(bits, 80), rounds, "{} bits", bits);
+        }
+        assert_eq!(miller_rabin_rounds(8, 80), 40);
+        assert!(miller_rabin_rounds(1024, 100) < miller_rabin_rounds(512, 100));
+    }
+
     #[test]
     fn factorization_test() {
         assert_eq!(factorization(2), [2]);
@@ -185,9 +605,88 @@ mod tests {
             test(x);
         }
 
+        assert_eq!(factorization(1), []);
+
         for _ in 0..100 {
             let x = rand::thread_rng().gen_range(2, u64::MAX as u128);
             test(x);
         }
     }
+
+    #[test]
+    fn try_factorization_test() {
+        assert_eq!(try_factorization(0), Err(FactorError::Zero));
+        assert_eq!(try_factorization(1), Ok(vec![]));
+        assert_eq!(try_factorization(12), Ok(vec![2, 2, 3]));
+        assert_eq!(try_factorize_biguint(&0u32.into()), Err(FactorError::Zero));
+        assert_eq!(try_factorize_biguint(&1u32.into()), Ok(vec![]));
+        assert_eq!(try_factorize(0), Err(FactorError::Zero));
+        assert!(try_factorize(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn seeded_rng_is_reproducible() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let n = 1000003_u128 * 1000033 * 1000037 * 1000039;
+        let rho = |seed| pollard_rho_with_rng(n, &mut StdRng::seed_from_u64(seed));
+        for seed in 0..10 {
+            assert_eq!(rho(seed), rho(seed));
+        }
+
+        let prime = |seed| gen_prime(128, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(prime(1), prime(1));
+
+        let factor = |seed| find_factor(n, &mut StdRng::seed_from_u64(seed));
+        for seed in 0..10 {
+            assert_eq!(factor(seed), factor(seed));
+        }
+
+        // The whole factorization draws the same values from the rng.
+        let draw_after = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            factorization_with_rng(n, &mut rng);
+            rng.gen::<u64>()
+        };
+        assert_eq!(draw_after(7), draw_after(7));
+    }
+
+    #[test]
+    fn pollard_rho_semiprime() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..5 {
+            let p: u128 = gen_prime(44, &mut rng).try_into().unwrap();
+            let q: u128 = gen_prime(64, &mut rng).try_into().unwrap();
+            let mut fs = vec![p, q];
+            fs.sort();
+            assert_eq!(factorization(p * q), fs);
+        }
+    }
+
+    #[test]
+    fn factorize_biguint_test() {
+        let test = |x: &BigUint| {
+            let fs = factorize_biguint(x);
+            assert!(fs.iter().all(is_prime));
+            assert!(fs.windows(2).all(|w| w[0] <= w[1]));
+            assert_eq!(&fs.iter().product::<BigUint>(), x);
+        };
+
+        for x in 2..100u32 {
+            test(&x.into());
+        }
+
+        let p = BigUint::from(241393502644931236824083437316691947053_u128);
+        let n = &p * 1000003u32 * 65537u32 * 65537u32;
+        assert_eq!(
+            factorize_biguint(&n),
+            [65537u32.into(), 65537u32.into(), 1000003u32.into(), p]
+        );
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let x = gen_prime(150, &mut rng) * rng.gen_range(2u64, 1 << 24);
+            test(&x);
+        }
+    }
 }
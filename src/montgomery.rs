@@ -0,0 +1,217 @@
+use crate::mul_mod;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Montgomery64 {
+    n: u64,
+    n_inv: u64,
+    r2: u64,
+}
+
+impl Montgomery64 {
+    pub fn new(n: u64) -> Self {
+        assert!(n & 1 == 1, "Montgomery modulus must be odd");
+        let mut n_inv = n;
+        for _ in 0..5 {
+            n_inv = n_inv.wrapping_mul(2u64.wrapping_sub(n.wrapping_mul(n_inv)));
+        }
+        let r = (1u128 << 64) % n as u128;
+        let r2 = (r * r % n as u128) as u64;
+        Montgomery64 { n, n_inv, r2 }
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.n
+    }
+
+    fn reduce(&self, t: u128) -> u64 {
+        let (hi, lo) = ((t >> 64) as u64, t as u64);
+        let m = lo.wrapping_mul(self.n_inv);
+        let mh = ((m as u128 * self.n as u128) >> 64) as u64;
+        if hi >= mh {
+            hi - mh
+        } else {
+            hi.wrapping_sub(mh).wrapping_add(self.n)
+        }
+    }
+
+    pub fn to_montgomery(&self, a: u64) -> u64 {
+        self.mul(a % self.n, self.r2)
+    }
+
+    pub fn from_montgomery(&self, a: u64) -> u64 {
+        self.reduce(a as u128)
+    }
+
+    pub fn one(&self) -> u64 {
+        self.to_montgomery(1)
+    }
+
+    pub fn mul(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    pub fn add(&self, a: u64, b: u64) -> u64 {
+        if a >= self.n - b {
+            a - (self.n - b)
+        } else {
+            a + b
+        }
+    }
+
+    pub fn sub(&self, a: u64, b: u64) -> u64 {
+        if a >= b {
+            a - b
+        } else {
+            self.n - (b - a)
+        }
+    }
+
+    pub fn pow(&self, mut a: u64, mut e: u64) -> u64 {
+        let mut ret = self.one();
+        while e > 0 {
+            if e & 1 == 1 {
+                ret = self.mul(ret, a);
+            }
+            a = self.mul(a, a);
+            e >>= 1;
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Montgomery128 {
+    n: u128,
+    n_inv: u128,
+    r2: u128,
+}
+
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a1, a0) = (a >> 64, a & MASK);
+    let (b1, b0) = (b >> 64, b & MASK);
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+    let mid = (p00 >> 64) + (p01 & MASK) + (p10 & MASK);
+    let lo = (p00 & MASK) | (mid << 64);
+    let hi = p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+    (hi, lo)
+}
+
+impl Montgomery128 {
+    pub fn new(n: u128) -> Self {
+        assert!(n & 1 == 1, "Montgomery modulus must be odd");
+        let mut n_inv = n;
+        for _ in 0..6 {
+            n_inv = n_inv.wrapping_mul(2u128.wrapping_sub(n.wrapping_mul(n_inv)));
+        }
+        let r = 0u128.wrapping_sub(n) % n;
+        let r2 = mul_mod(r, r, n);
+        Montgomery128 { n, n_inv, r2 }
+    }
+
+    pub fn modulus(&self) -> u128 {
+        self.n
+    }
+
+    fn reduce(&self, hi: u128, lo: u128) -> u128 {
+        let m = lo.wrapping_mul(self.n_inv);
+        let (mh, _) = mul_wide(m, self.n);
+        if hi >= mh {
+            hi - mh
+        } else {
+            hi.wrapping_sub(mh).wrapping_add(self.n)
+        }
+    }
+
+    pub fn to_montgomery(&self, a: u128) -> u128 {
+        self.mul(a % self.n, self.r2)
+    }
+
+    pub fn from_montgomery(&self, a: u128) -> u128 {
+        self.reduce(0, a)
+    }
+
+    pub fn one(&self) -> u128 {
+        self.to_montgomery(1)
+    }
+
+    pub fn mul(&self, a: u128, b: u128) -> u128 {
+        let (hi, lo) = mul_wide(a, b);
+        self.reduce(hi, lo)
+    }
+
+    pub fn add(&self, a: u128, b: u128) -> u128 {
+        if a >= self.n - b {
+            a - (self.n - b)
+        } else {
+            a + b
+        }
+    }
+
+    pub fn sub(&self, a: u128, b: u128) -> u128 {
+        if a >= b {
+            a - b
+        } else {
+            self.n - (b - a)
+        }
+    }
+
+    // Halving commutes with the Montgomery transform, so this works on either form.
+    pub fn half(&self, a: u128) -> u128 {
+        if a & 1 == 0 {
+            a >> 1
+        } else {
+            (a >> 1) + (self.n >> 1) + 1
+        }
+    }
+
+    pub fn pow(&self, mut a: u128, mut e: u128) -> u128 {
+        let mut ret = self.one();
+        while e > 0 {
+            if e & 1 == 1 {
+                ret = self.mul(ret, a);
+            }
+            a = self.mul(a, a);
+            e >>= 1;
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use rand::Rng;
+
+    #[test]
+    fn montgomery_matches_mul_mod() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10000 {
+            let n = rng.gen::<u128>() | 1;
+            let (a, b) = (rng.gen::<u128>() % n, rng.gen::<u128>() % n);
+            let m = Montgomery128::new(n);
+            let (am, bm) = (m.to_montgomery(a), m.to_montgomery(b));
+            assert_eq!(m.from_montgomery(am), a);
+            assert_eq!(m.from_montgomery(m.mul(am, bm)), mul_mod(a, b, n));
+
+            let n = rng.gen::<u64>() | 1;
+            let (a, b) = (rng.gen::<u64>() % n, rng.gen::<u64>() % n);
+            let m = Montgomery64::new(n);
+            let (am, bm) = (m.to_montgomery(a), m.to_montgomery(b));
+            assert_eq!(m.from_montgomery(am), a);
+            assert_eq!(
+                m.from_montgomery(m.mul(am, bm)) as u128,
+                mul_mod(a as u128, b as u128, n as u128)
+            );
+        }
+
+        let m = Montgomery128::new(u128::MAX);
+        let a = m.to_montgomery(u128::MAX - 1);
+        assert_eq!(m.from_montgomery(m.mul(a, a)), 1);
+        let m = Montgomery64::new(1);
+        assert_eq!(m.from_montgomery(m.mul(m.one(), m.one())), 0);
+    }
+}
@@ -0,0 +1,385 @@
+// Pollard's p - 1 and Williams' p + 1 methods, written once over a small
+// modular arithmetic trait so that both u128 and BigUint moduli share them.
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::One;
+
+use crate::{gcd, sieve::primes_up_to, Montgomery128};
+
+// Stage 2 of p + 1 and ECM writes primes above B1 as k * D ± j with j < D / 2.
+pub(crate) const STAGE2_D: u64 = 210;
+
+// Stage 1 takes one gcd per this many primes, replaying the block prime by
+// prime if every factor shows up at once.
+const STAGE1_BLOCK: usize = 64;
+
+const P_PLUS_1_SEEDS: [u64; 3] = [3, 4, 6];
+
+pub(crate) trait Ring {
+    type Elem: Clone + PartialEq;
+
+    fn elem(&self, a: u64) -> Self::Elem;
+    fn mul(&self, a: &Self::Elem, b: &Self::Elem) -> Self::Elem;
+    fn sub(&self, a: &Self::Elem, b: &Self::Elem) -> Self::Elem;
+    // gcd of the represented value with the modulus.
+    fn gcd(&self, a: &Self::Elem) -> Self::Elem;
+    fn is_modulus(&self, g: &Self::Elem) -> bool;
+    fn is_one(&self, g: &Self::Elem) -> bool;
+
+    fn pow(&self, a: &Self::Elem, mut e: u64) -> Self::Elem {
+        let mut ret = self.elem(1);
+        let mut a = a.clone();
+        while e > 0 {
+            if e & 1 == 1 {
+                ret = self.mul(&ret, &a);
+            }
+            a = self.mul(&a, &a);
+            e >>= 1;
+        }
+        ret
+    }
+
+    // V_k(v) of the Lucas sequence with P = v, Q = 1.
+    fn lucas_v(&self, v: &Self::Elem, k: u64) -> Self::Elem {
+        let two = self.elem(2);
+        let mut x = two.clone();
+        let mut y = v.clone();
+        for i in (0..64 - k.leading_zeros()).rev() {
+            if k >> i & 1 == 1 {
+                x = self.sub(&self.mul(&x, &y), v);
+                y = self.sub(&self.mul(&y, &y), &two);
+            } else {
+                y = self.sub(&self.mul(&x, &y), v);
+                x = self.sub(&self.mul(&x, &x), &two);
+            }
+        }
+        x
+    }
+}
+
+impl Ring for Montgomery128 {
+    type Elem = u128;
+
+    fn elem(&self, a: u64) -> u128 {
+        self.to_montgomery(a as u128)
+    }
+
+    fn mul(&self, a: &u128, b: &u128) -> u128 {
+        Montgomery128::mul(self, *a, *b)
+    }
+
+    fn sub(&self, a: &u128, b: &u128) -> u128 {
+        Montgomery128::sub(self, *a, *b)
+    }
+
+    // R is invertible modulo n, so the Montgomery form has the same gcd.
+    fn gcd(&self, a: &u128) -> u128 {
+        gcd(*a, self.modulus())
+    }
+
+    fn is_modulus(&self, g: &u128) -> bool {
+        *g == self.modulus()
+    }
+
+    fn is_one(&self, g: &u128) -> bool {
+        *g == 1
+    }
+}
+
+pub(crate) struct BigRing<'a>(pub(crate) &'a BigUint);
+
+impl Ring for BigRing<'_> {
+    type Elem = BigUint;
+
+    fn elem(&self, a: u64) -> BigUint {
+        BigUint::from(a) % self.0
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b % self.0
+    }
+
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + self.0 - b) % self.0
+    }
+
+    fn gcd(&self, a: &BigUint) -> BigUint {
+        a.gcd(self.0)
+    }
+
+    fn is_modulus(&self, g: &BigUint) -> bool {
+        g == self.0
+    }
+
+    fn is_one(&self, g: &BigUint) -> bool {
+        g.is_one()
+    }
+}
+
+fn prime_power(p: u64, bound: u64) -> u64 {
+    let mut pk = p;
+    while pk <= bound / p {
+        pk *= p;
+    }
+    pk
+}
+
+// Raises x to every prime power up to b1 with `step`, watching gcd(x - c, n).
+fn stage1<R: Ring>(
+    ring: &R,
+    mut x: R::Elem,
+    c: &R::Elem,
+    primes: &[u64],
+    b1: u64,
+    step: impl Fn(&R::Elem, u64) -> R::Elem,
+) -> Result<R::Elem, Option<R::Elem>> {
+    let stage1_primes = &primes[..primes.partition_point(|&p| p <= b1)];
+    for block in stage1_primes.chunks(STAGE1_BLOCK) {
+        let saved = x.clone();
+        for &p in block.iter() {
+            x = step(&x, prime_power(p, b1));
+        }
+        let g = ring.gcd(&ring.sub(&x, c));
+        if ring.is_one(&g) {
+            continue;
+        }
+        if !ring.is_modulus(&g) {
+            return Err(Some(g));
+        }
+
+        x = saved;
+        for &p in block.iter() {
+            x = step(&x, prime_power(p, b1));
+            let g = ring.gcd(&ring.sub(&x, c));
+            if !ring.is_one(&g) {
+                return Err(if ring.is_modulus(&g) { None } else { Some(g) });
+            }
+        }
+    }
+    Ok(x)
+}
+
+fn nontrivial<R: Ring>(ring: &R, g: R::Elem) -> Option<R::Elem> {
+    if ring.is_one(&g) || ring.is_modulus(&g) {
+        None
+    } else {
+        Some(g)
+    }
+}
+
+// `primes` must reach max(b1, b2) here and in p_plus_1.
+fn p_minus_1<R: Ring>(ring: &R, primes: &[u64], b1: u64, b2: u64) -> Option<R::Elem> {
+    let one = ring.elem(1);
+    let a = match stage1(ring, ring.elem(2), &one, primes, b1, |x, e| ring.pow(x, e)) {
+        Ok(a) => a,
+        Err(g) => return g,
+    };
+
+    // Stage 2: step through the remaining primes by their gaps, using a
+    // table of a^d for even d.
+    let rest: Vec<u64> = primes
+        .iter()
+        .copied()
+        .skip_while(|&p| p <= b1)
+        .take_while(|&p| p <= b2)
+        .collect();
+    let first = *rest.first()?;
+    let max_gap = rest.windows(2).map(|w| w[1] - w[0]).max().unwrap_or(0);
+    let a2 = ring.mul(&a, &a);
+    let mut table = vec![one.clone()];
+    while (table.len() as u64 - 1) * 2 < max_gap {
+        let next = ring.mul(table.last().unwrap(), &a2);
+        table.push(next);
+    }
+
+    let mut x = ring.pow(&a, first);
+    let mut acc = one.clone();
+    for (i, &q) in rest.iter().enumerate() {
+        acc = ring.mul(&acc, &ring.sub(&x, &one));
+        if let Some(&next) = rest.get(i + 1) {
+            x = ring.mul(&x, &table[((next - q) / 2) as usize]);
+        }
+    }
+    nontrivial(ring, ring.gcd(&acc))
+}
+
+fn p_plus_1<R: Ring>(ring: &R, primes: &[u64], seed: u64, b1: u64, b2: u64) -> Option<R::Elem> {
+    let two = ring.elem(2);
+    let b1 = b1.max(STAGE2_D / 2);
+    let w = match stage1(ring, ring.elem(seed), &two, primes, b1, |v, e| {
+        ring.lucas_v(v, e)
+    }) {
+        Ok(w) => w,
+        Err(g) => return g,
+    };
+
+    // V_q(W) = 2 for q = kD ± j exactly when V_kD(W) = V_j(W), and
+    // V_(m+n) = V_m V_n - V_(m-n).
+    let acc = stage2(
+        ring,
+        primes,
+        b1,
+        b2,
+        |k| ring.lucas_v(&w, k),
+        |v, u, d| ring.sub(&ring.mul(v, u), d),
+        |v, u| ring.sub(v, u),
+    );
+    nontrivial(ring, ring.gcd(&acc))
+}
+
+fn p_plus_1_seeds<R: Ring>(ring: &R, primes: &[u64], b1: u64, b2: u64) -> Option<R::Elem> {
+    P_PLUS_1_SEEDS
+        .iter()
+        .find_map(|&seed| p_plus_1(ring, primes, seed, b1, b2))
+}
+
+// Stage 2 for a group with a differential addition add(P, Q, P - Q), given
+// mul(k) = kW for the stage 1 result W. Every prime q in (lo, hi] is kD ± j
+// for an odd j < D / 2, and diff(kD W, j W) is 0 mod p whenever qW is the
+// identity mod p, so the product of the diffs picks up p. lo must be at
+// least D / 2.
+pub(crate) fn stage2<R: Ring, P: Clone>(
+    ring: &R,
+    primes: &[u64],
+    lo: u64,
+    hi: u64,
+    mul: impl Fn(u64) -> P,
+    add: impl Fn(&P, &P, &P) -> P,
+    diff: impl Fn(&P, &P) -> R::Elem,
+) -> R::Elem {
+    let half = STAGE2_D / 2;
+
+    // baby[j / 2] = jW for odd j < D / 2.
+    let (w, w2) = (mul(1), mul(2));
+    let mut baby = vec![w.clone(), add(&w2, &w, &w)];
+    while (baby.len() as u64) * 2 < half {
+        let k = baby.len();
+        let next = add(&baby[k - 1], &w2, &baby[k - 2]);
+        baby.push(next);
+    }
+
+    let dw = mul(STAGE2_D);
+    let mut k = (lo / STAGE2_D).max(1);
+    let mut r = mul(k * STAGE2_D);
+    let mut r_next = mul((k + 1) * STAGE2_D);
+
+    let mut rest = primes
+        .iter()
+        .copied()
+        .skip_while(|&q| q <= lo)
+        .take_while(|&q| q <= hi)
+        .peekable();
+    let mut acc = ring.elem(1);
+    while rest.peek().is_some() {
+        let center = k * STAGE2_D;
+        while let Some(&q) = rest.peek() {
+            if q > center + half {
+                break;
+            }
+            rest.next();
+            let j = q.abs_diff(center);
+            acc = ring.mul(&acc, &diff(&r, &baby[(j / 2) as usize]));
+        }
+        let next = add(&r_next, &dw, &r);
+        r = std::mem::replace(&mut r_next, next);
+        k += 1;
+    }
+    acc
+}
+
+fn with_ring(n: u128, f: impl FnOnce(&Montgomery128) -> Option<u128>) -> Option<u128> {
+    if n < 4 {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+    f(&Montgomery128::new(n))
+}
+
+fn with_big_ring(n: &BigUint, f: impl FnOnce(&BigRing) -> Option<BigUint>) -> Option<BigUint> {
+    if n < &BigUint::from(4u32) {
+        return None;
+    }
+    if n.is_even() {
+        return Some(2u32.into());
+    }
+    f(&BigRing(n))
+}
+
+pub fn pollard_p_minus_1(n: u128, b1: u64, b2: u64) -> Option<u128> {
+    with_ring(n, |ring| p_minus_1(ring, &primes_up_to(b1.max(b2)), b1, b2))
+}
+
+pub fn pollard_p_minus_1_biguint(n: &BigUint, b1: u64, b2: u64) -> Option<BigUint> {
+    with_big_ring(n, |ring| p_minus_1(ring, &primes_up_to(b1.max(b2)), b1, b2))
+}
+
+pub fn williams_p_plus_1(n: u128, b1: u64, b2: u64) -> Option<u128> {
+    let primes = primes_up_to(b1.max(b2).max(STAGE2_D));
+    with_ring(n, |ring| p_plus_1_seeds(ring, &primes, b1, b2))
+}
+
+pub fn williams_p_plus_1_biguint(n: &BigUint, b1: u64, b2: u64) -> Option<BigUint> {
+    let primes = primes_up_to(b1.max(b2).max(STAGE2_D));
+    with_big_ring(n, |ring| p_plus_1_seeds(ring, &primes, b1, b2))
+}
+
+// p - 1 and then p + 1 with a shared prime list, for the factoring pre-pass.
+pub(crate) fn smooth_prepass(n: u128, primes: &[u64], b1: u64, b2: u64) -> Option<u128> {
+    with_ring(n, |ring| {
+        p_minus_1(ring, primes, b1, b2).or_else(|| p_plus_1_seeds(ring, primes, b1, b2))
+    })
+}
+
+pub(crate) fn smooth_prepass_biguint(
+    n: &BigUint,
+    primes: &[u64],
+    b1: u64,
+    b2: u64,
+) -> Option<BigUint> {
+    with_big_ring(n, |ring| {
+        p_minus_1(ring, primes, b1, b2).or_else(|| p_plus_1_seeds(ring, primes, b1, b2))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    // Neither q - 1 nor q + 1 is smooth.
+    const Q: u128 = 1000000000000000003;
+
+    #[test]
+    fn p_minus_1() {
+        // 2^31 - 2 = 2 * 3^2 * 7 * 11 * 31 * 151 * 331.
+        let p = 2147483647_u128;
+        assert_eq!(pollard_p_minus_1(p * Q, 1000, 10000), Some(p));
+
+        // p - 1 = 2^4 * 3 * 5 * 11 * 29 * 151 * 86501 needs stage 2.
+        let p = 1000000000561_u128;
+        assert_eq!(pollard_p_minus_1(p * Q, 1000, 1000), None);
+        assert_eq!(pollard_p_minus_1(p * Q, 1000, 100000), Some(p));
+
+        let n = BigUint::from(p) * BigUint::from(241393502644931236824083437316691947053_u128);
+        assert_eq!(pollard_p_minus_1_biguint(&n, 1000, 100000), Some(p.into()));
+    }
+
+    #[test]
+    fn p_plus_1() {
+        // p + 1 = 2 * 23 * 53 * 89 * 127 * 271 * 359 * 373.
+        let p = 1000000000333057_u128;
+        assert_eq!(williams_p_plus_1(p * Q, 1000, 1000), Some(p));
+
+        // p + 1 = 2^6 * 13 * 41 * 61 * 157 * 3061 needs stage 2, and p - 1
+        // has a large prime factor.
+        let p = 1000000000063_u128;
+        assert_eq!(williams_p_plus_1(p * Q, 1000, 1000), None);
+        assert_eq!(williams_p_plus_1(p * Q, 1000, 10000), Some(p));
+
+        let n = BigUint::from(p) * BigUint::from(241393502644931236824083437316691947053_u128);
+        assert_eq!(williams_p_plus_1_biguint(&n, 1000, 10000), Some(p.into()));
+    }
+}
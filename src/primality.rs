@@ -0,0 +1,400 @@
+use std::convert::TryFrom;
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+use crate::{Montgomery128, Montgomery64};
+
+const SMALL_PRIMES: [u64; 15] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+
+// Jim Sinclair's bases, deterministic for all n < 2^64.
+const U64_BASES: [u64; 7] = [2, 325, 9375, 28178, 450775, 9780504, 1795265022];
+
+pub fn is_prime_u64(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for &p in SMALL_PRIMES.iter() {
+        if n == p {
+            return true;
+        }
+        if n.is_multiple_of(p) {
+            return false;
+        }
+    }
+    if n < 53 * 53 {
+        return true;
+    }
+
+    let m = Montgomery64::new(n);
+    let one = m.one();
+    let t = n - 1;
+    let minus_one = m.sub(0, one);
+    let r = t.trailing_zeros();
+    let d = t >> r;
+
+    'outer: for &a in U64_BASES.iter() {
+        let a = a % n;
+        if a == 0 {
+            continue;
+        }
+        let mut x = m.pow(m.to_montgomery(a), d);
+        if x == one || x == minus_one {
+            continue;
+        }
+        for _ in 1..r {
+            x = m.mul(x, x);
+            if x == minus_one {
+                continue 'outer;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+pub fn is_prime_u128(n: u128) -> bool {
+    if let Ok(n) = u64::try_from(n) {
+        return is_prime_u64(n);
+    }
+    for &p in SMALL_PRIMES.iter() {
+        if n.is_multiple_of(p as u128) {
+            return false;
+        }
+    }
+    strong_probable_prime_u128(n, 2) && strong_lucas_u128(n)
+}
+
+fn sub_mod(a: u128, b: u128, m: u128) -> u128 {
+    if a >= b {
+        a - b
+    } else {
+        m - (b - a)
+    }
+}
+
+fn strong_probable_prime_u128(n: u128, a: u128) -> bool {
+    let m = Montgomery128::new(n);
+    let one = m.one();
+    let minus_one = m.sub(0, one);
+    let t = n - 1;
+    let r = t.trailing_zeros();
+    let d = t >> r;
+
+    let mut x = m.pow(m.to_montgomery(a), d);
+    if x == one || x == minus_one {
+        return true;
+    }
+    for _ in 1..r {
+        x = m.mul(x, x);
+        if x == minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+pub(crate) fn isqrt_u128(n: u128) -> u128 {
+    let mut x = (n as f64).sqrt() as u128;
+    while x.checked_mul(x).is_none_or(|sq| sq > n) {
+        x -= 1;
+    }
+    while (x + 1).checked_mul(x + 1).is_some_and(|sq| sq <= n) {
+        x += 1;
+    }
+    x
+}
+
+pub(crate) fn icbrt_u64(n: u64) -> u64 {
+    let mut x = (n as f64).cbrt() as u64;
+    while x * x * x > n {
+        x -= 1;
+    }
+    while ((x + 1) as u128).pow(3) <= n as u128 {
+        x += 1;
+    }
+    x
+}
+
+pub(crate) fn jacobi_u128(a: u128, n: u128) -> i32 {
+    let mut a = a % n;
+    let mut n = n;
+    let mut ret = 1;
+    while a != 0 {
+        while a & 1 == 0 {
+            a >>= 1;
+            if n & 7 == 3 || n & 7 == 5 {
+                ret = -ret;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a & 3 == 3 && n & 3 == 3 {
+            ret = -ret;
+        }
+        a %= n;
+    }
+    if n == 1 {
+        ret
+    } else {
+        0
+    }
+}
+
+fn signed_mod(x: i64, n: u128) -> u128 {
+    if x >= 0 {
+        x as u128 % n
+    } else {
+        sub_mod(0, x.unsigned_abs() as u128 % n, n)
+    }
+}
+
+// Strong Lucas probable prime test with Selfridge's method A parameters.
+fn strong_lucas_u128(n: u128) -> bool {
+    let s = isqrt_u128(n);
+    if s * s == n {
+        return false;
+    }
+
+    let mut d: i64 = 5;
+    loop {
+        match jacobi_u128(signed_mod(d, n), n) {
+            -1 => break,
+            0 if d.unsigned_abs() as u128 != n => return false,
+            _ => d = if d > 0 { -d - 2 } else { -d + 2 },
+        }
+    }
+    let m = Montgomery128::new(n);
+    let q = m.to_montgomery(signed_mod((1 - d) / 4, n));
+    let d = m.to_montgomery(signed_mod(d, n));
+
+    let t = n + 1;
+    let r = t.trailing_zeros();
+    let k = t >> r;
+
+    let mut u = m.one();
+    let mut v = m.one();
+    let mut qk = q;
+    for i in (0..127 - k.leading_zeros()).rev() {
+        u = m.mul(u, v);
+        v = m.sub(m.mul(v, v), m.add(qk, qk));
+        qk = m.mul(qk, qk);
+        if k >> i & 1 == 1 {
+            let nu = m.half(m.add(u, v));
+            v = m.half(m.add(m.mul(d, u), v));
+            u = nu;
+            qk = m.mul(qk, q);
+        }
+    }
+
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..r {
+        v = m.sub(m.mul(v, v), m.add(qk, qk));
+        qk = m.mul(qk, qk);
+        if v == 0 {
+            return true;
+        }
+    }
+    false
+}
+
+pub fn bpsw_test(n: &BigUint) -> bool {
+    if let Ok(n) = u128::try_from(n) {
+        return is_prime_u128(n);
+    }
+    for &p in SMALL_PRIMES.iter() {
+        if (n % p).is_zero() {
+            return false;
+        }
+    }
+    is_strong_probable_prime(n, &BigUint::from(2u32)) && strong_lucas(n)
+}
+
+// A base that is a multiple of n carries no information, so it is rejected
+// even for prime n.
+pub fn is_strong_probable_prime(n: &BigUint, base: &BigUint) -> bool {
+    if n.is_zero() || n.is_one() {
+        return false;
+    }
+    let a = base % n;
+    if a.is_zero() {
+        return false;
+    }
+    if !n.bit(0) {
+        return n == &BigUint::from(2u32);
+    }
+
+    let t: BigUint = n - 1u32;
+    let r = t.trailing_zeros().unwrap();
+    let d = &t >> r;
+
+    let mut x = a.modpow(&d, n);
+    if x.is_one() || x == t {
+        return true;
+    }
+    for _ in 1..r {
+        x = &x * &x % n;
+        if x == t {
+            return true;
+        }
+    }
+    false
+}
+
+pub(crate) fn jacobi(a: &BigUint, n: &BigUint) -> i32 {
+    let mut a = a % n;
+    let mut n = n.clone();
+    let mut ret = 1;
+    while !a.is_zero() {
+        let z = a.trailing_zeros().unwrap();
+        a >>= z;
+        let n8 = (&n % 8u32).to_u32_digits().first().copied().unwrap_or(0);
+        if z & 1 == 1 && (n8 == 3 || n8 == 5) {
+            ret = -ret;
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a.bit(0) && a.bit(1) && n.bit(0) && n.bit(1) {
+            ret = -ret;
+        }
+        a %= &n;
+    }
+    if n.is_one() {
+        ret
+    } else {
+        0
+    }
+}
+
+fn signed_to_mod(x: i64, n: &BigUint) -> BigUint {
+    let r = BigUint::from(x.unsigned_abs()) % n;
+    if x < 0 && !r.is_zero() {
+        n - r
+    } else {
+        r
+    }
+}
+
+fn half(x: BigUint, n: &BigUint) -> BigUint {
+    if x.bit(0) {
+        (x + n) >> 1
+    } else {
+        x >> 1
+    }
+}
+
+fn strong_lucas(n: &BigUint) -> bool {
+    let s = n.sqrt();
+    if &(&s * &s) == n {
+        return false;
+    }
+
+    let mut d: i64 = 5;
+    loop {
+        match jacobi(&signed_to_mod(d, n), n) {
+            -1 => break,
+            0 if &BigUint::from(d.unsigned_abs()) != n => return false,
+            _ => d = if d > 0 { -d - 2 } else { -d + 2 },
+        }
+    }
+    let q = signed_to_mod((1 - d) / 4, n);
+    let d = signed_to_mod(d, n);
+
+    let t: BigUint = n + 1u32;
+    let r = t.trailing_zeros().unwrap();
+    let k = &t >> r;
+
+    let mut u = BigUint::one();
+    let mut v = BigUint::one();
+    let mut qk = q.clone();
+    for i in (0..k.bits() - 1).rev() {
+        u = &u * &v % n;
+        v = (&v * &v + n + n - &qk - &qk) % n;
+        qk = &qk * &qk % n;
+        if k.bit(i) {
+            let nu = half((&u + &v) % n, n);
+            v = half((&d * &u + &v) % n, n);
+            u = nu;
+            qk = &qk * &q % n;
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..r {
+        v = (&v * &v + n + n - &qk - &qk) % n;
+        qk = &qk * &qk % n;
+        if v.is_zero() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::RandBigInt;
+    use num_traits::One;
+    use rand::Rng;
+
+    #[test]
+    fn native_matches_biguint() {
+        for n in 0..10000u64 {
+            assert_eq!(is_prime_u64(n), is_prime(&n.into()), "{}", n);
+            assert_eq!(is_prime_u128(n as u128), is_prime_u64(n), "{}", n);
+        }
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let n: u128 = rng.gen();
+            assert_eq!(is_prime_u128(n), is_prime(&n.into()), "{}", n);
+            let n: u64 = rng.gen::<u64>() | 1;
+            assert_eq!(is_prime_u64(n), is_prime(&n.into()), "{}", n);
+        }
+    }
+
+    #[test]
+    fn pseudoprimes() {
+        // Strong pseudoprimes to many small bases, and Carmichael numbers.
+        let composites = [
+            561u128,
+            3215031751,
+            2152302898747,
+            3474749660383,
+            341550071728321,
+            3825123056546413051,
+            318665857834031151167461,
+            3317044064679887385961981,
+        ];
+        for &c in composites.iter() {
+            assert!(!is_prime_u128(c), "{}", c);
+        }
+
+        assert!(is_prime_u64(18446744073709551557));
+        assert!(is_prime_u128(u128::MAX - 158));
+        assert!(is_prime_u128(170141183460469231731687303715884105727));
+    }
+
+    #[test]
+    fn bpsw() {
+        for n in 0..2000u32 {
+            assert_eq!(bpsw_test(&n.into()), is_prime_u64(n as u64), "{}", n);
+        }
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let n = rng.gen_biguint(256) | BigUint::one();
+            assert_eq!(bpsw_test(&n), is_prime(&n), "{}", n);
+        }
+
+        let p = BigUint::from(241393502644931236824083437316691947053_u128);
+        let q = BigUint::from(203851774287909279562700874830405601907_u128);
+        assert!(bpsw_test(&((BigUint::one() << 521) - 1u32)));
+        assert!(!bpsw_test(&(&p * &q)));
+        assert!(!bpsw_test(&(&p * &p)));
+        assert!(is_prime_with(&p, PrimalityTest::Bpsw));
+        assert!(!is_prime_with(&(&p * &q), PrimalityTest::MillerRabin(20)));
+    }
+}
@@ -0,0 +1,319 @@
+use num_bigint::{BigUint, RandBigInt};
+use num_traits::{One, ToPrimitive};
+use rand::Rng;
+
+use crate::{
+    is_prime_u64, is_prime_with_rng, is_random_prime_candidate, is_strong_probable_prime,
+    sieve::primes_up_to,
+};
+
+// Candidates are sieved by the primes up to this bound before testing.
+const SIEVE_BOUND: u64 = 1 << 14;
+const SIEVE_WINDOW: usize = 1 << 12;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TopBits {
+    One,
+    // The product of two such primes has exactly twice as many bits.
+    Two,
+}
+
+pub fn gen_prime_exact_bits(bits: usize, top: TopBits, rng: &mut impl Rng) -> BigUint {
+    assert!(bits >= 2, "no prime has fewer than 2 bits");
+    let hi = BigUint::one() << bits;
+    let lo = match top {
+        TopBits::Two if bits >= 3 => BigUint::from(3u32) << (bits - 2),
+        _ => BigUint::one() << (bits - 1),
+    };
+    gen_prime_in_range(&lo, &hi, rng).unwrap()
+}
+
+// The first prime at or after a random point of [lo, hi), wrapping around,
+// or None if the range has no primes.
+pub fn gen_prime_in_range(lo: &BigUint, hi: &BigUint, rng: &mut impl Rng) -> Option<BigUint> {
+    let lo = lo.max(&BigUint::from(2u32)).clone();
+    if &lo >= hi {
+        return None;
+    }
+    let start = rng.gen_biguint_range(&lo, hi);
+    let primes = primes_up_to(SIEVE_BOUND);
+    search(&start, hi, &primes, false, true, rng)
+        .or_else(|| search(&lo, &start, &primes, false, true, rng))
+}
+
+pub fn gen_safe_prime(bits: usize, rng: &mut impl Rng) -> BigUint {
+    assert!(bits >= 3, "no safe prime has fewer than 3 bits");
+    (gen_sophie_germain_prime(bits - 1, rng) << 1) + 1u32
+}
+
+// A prime q of exactly `bits` bits with 2q + 1 also prime.
+pub fn gen_sophie_germain_prime(bits: usize, rng: &mut impl Rng) -> BigUint {
+    assert!(bits >= 2, "no prime has fewer than 2 bits");
+    let lo = BigUint::one() << (bits - 1);
+    let hi = BigUint::one() << bits;
+    let start = rng.gen_biguint_range(&lo, &hi);
+    let primes = primes_up_to(SIEVE_BOUND);
+    search(&start, &hi, &primes, true, true, rng)
+        .or_else(|| search(&lo, &start, &primes, true, true, rng))
+        .unwrap()
+}
+
+pub fn is_safe_prime(p: &BigUint) -> bool {
+    is_safe_prime_with_rng(p, &mut rand::thread_rng())
+}
+
+pub fn is_safe_prime_with_rng(p: &BigUint, rng: &mut impl Rng) -> bool {
+    p.bit(0)
+        && p > &BigUint::from(3u32)
+        && is_prime_with_rng(&(p >> 1), rng)
+        && is_prime_with_rng(p, rng)
+}
+
+// The smallest prime above n.
+pub fn next_prime(n: &BigUint) -> BigUint {
+    next_prime_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn next_prime_with_rng(n: &BigUint, rng: &mut impl Rng) -> BigUint {
+    let primes = primes_up_to(SIEVE_BOUND);
+    // Bertrand's postulate: there is a prime in (n, 2n + 2].
+    search(&(n + 1u32), &((n << 1) + 3u32), &primes, false, false, rng).unwrap()
+}
+
+// The largest prime below n.
+pub fn prev_prime(n: &BigUint) -> Option<BigUint> {
+    prev_prime_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn prev_prime_with_rng(n: &BigUint, rng: &mut impl Rng) -> Option<BigUint> {
+    let primes = primes_up_to(SIEVE_BOUND);
+    search_down(&2u32.into(), n, &primes, rng)
+}
+
+// None if there is no prime above n below 2^64.
+pub fn next_prime_u64(n: u64) -> Option<u64> {
+    let primes = primes_up_to(SIEVE_BOUND);
+    let mut base = n.checked_add(1)?;
+    while base < u64::MAX {
+        let len = (u64::MAX - base).min(SIEVE_WINDOW as u64) as usize;
+        let composite = sieve_window(&base.into(), len, &primes, false);
+        let n = (0..len as u64)
+            .map(|i| base + i)
+            .find(|&n| !composite[(n - base) as usize] && is_prime_u64(n));
+        if n.is_some() {
+            return n;
+        }
+        base += len as u64;
+    }
+    None
+}
+
+pub fn prev_prime_u64(n: u64) -> Option<u64> {
+    let primes = primes_up_to(SIEVE_BOUND);
+    let mut top = n;
+    while top > 2 {
+        let len = (top - 2).min(SIEVE_WINDOW as u64) as usize;
+        let base = top - len as u64;
+        let composite = sieve_window(&base.into(), len, &primes, false);
+        let n = (0..len as u64)
+            .rev()
+            .map(|i| base + i)
+            .find(|&n| !composite[(n - base) as usize] && is_prime_u64(n));
+        if n.is_some() {
+            return n;
+        }
+        top = base;
+    }
+    None
+}
+
+// Smallest prime q in [from, to), sieving a window at a time. With `safe`,
+// 2q + 1 must be prime too and is sieved by the same primes. Only a random
+// `from` gets the average-case round count.
+fn search(
+    from: &BigUint,
+    to: &BigUint,
+    primes: &[u64],
+    safe: bool,
+    random: bool,
+    rng: &mut impl Rng,
+) -> Option<BigUint> {
+    let is_prime = |n: &BigUint, rng: &mut _| {
+        if random {
+            is_random_prime_candidate(n, rng)
+        } else {
+            is_prime_with_rng(n, rng)
+        }
+    };
+    let mut base = from.clone();
+    while &base < to {
+        let len = (to - &base).min(SIEVE_WINDOW.into()).to_usize().unwrap();
+        let composite = sieve_window(&base, len, primes, safe);
+
+        // A single base 2 round screens most survivors before the full tests.
+        let two = BigUint::from(2u32);
+        for (i, _) in composite.iter().enumerate().filter(|&(_, &c)| !c) {
+            let n = &base + i;
+            if !safe {
+                if is_prime(&n, rng) {
+                    return Some(n);
+                }
+                continue;
+            }
+            let p = (&n << 1) + 1u32;
+            if is_strong_probable_prime(&n, &two)
+                && is_strong_probable_prime(&p, &two)
+                && is_prime(&n, rng)
+                && is_prime(&p, rng)
+            {
+                return Some(n);
+            }
+        }
+        base += len;
+    }
+    None
+}
+
+// Largest prime in [from, to), sieving windows from the top down.
+fn search_down(
+    from: &BigUint,
+    to: &BigUint,
+    primes: &[u64],
+    rng: &mut impl Rng,
+) -> Option<BigUint> {
+    let mut top = to.clone();
+    while &top > from {
+        let len = (&top - from).min(SIEVE_WINDOW.into()).to_usize().unwrap();
+        let base = &top - len;
+        let composite = sieve_window(&base, len, primes, false);
+        for (i, _) in composite.iter().enumerate().rev().filter(|&(_, &c)| !c) {
+            let n = &base + i;
+            if is_prime_with_rng(&n, rng) {
+                return Some(n);
+            }
+        }
+        top = base;
+    }
+    None
+}
+
+// Marks the n in [base, base + len) with a factor in `primes` other than n
+// itself, and with `safe` also those where 2n + 1 has one.
+fn sieve_window(base: &BigUint, len: usize, primes: &[u64], safe: bool) -> Vec<bool> {
+    let small = base.to_u64();
+    let mut composite = vec![false; len];
+    for &p in primes.iter() {
+        let r = (base % p).to_u64().unwrap();
+        let mut i = ((p - r) % p) as usize;
+        // The prime itself is not ruled out.
+        if small.is_some_and(|b| b <= p && b + i as u64 == p) {
+            i += p as usize;
+        }
+        while i < len {
+            composite[i] = true;
+            i += p as usize;
+        }
+
+        // 2q + 1 = 0 mod p exactly when q = (p - 1) / 2 mod p.
+        if safe && p > 2 {
+            let mut i = ((p - 1) / 2 + p - r) as usize % p as usize;
+            if small.is_some_and(|b| b < p && 2 * (b + i as u64) + 1 == p) {
+                i += p as usize;
+            }
+            while i < len {
+                composite[i] = true;
+                i += p as usize;
+            }
+        }
+    }
+    composite
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn exact_bits() {
+        let mut rng = rand::thread_rng();
+        for &bits in [2, 3, 8, 64, 256].iter() {
+            let p = gen_prime_exact_bits(bits, TopBits::One, &mut rng);
+            assert_eq!(p.bits(), bits as u64);
+            assert!(is_prime(&p));
+
+            let p = gen_prime_exact_bits(bits, TopBits::Two, &mut rng);
+            assert_eq!(p.bits(), bits as u64);
+            assert!(bits < 3 || p.bit(bits as u64 - 2));
+            assert!(is_prime(&p));
+        }
+    }
+
+    #[test]
+    fn in_range() {
+        let mut rng = rand::thread_rng();
+        let r = |lo: u32, hi: u32, rng: &mut rand::rngs::ThreadRng| {
+            gen_prime_in_range(&lo.into(), &hi.into(), rng)
+        };
+        assert_eq!(r(0, 3, &mut rng), Some(BigUint::from(2u32)));
+        assert_eq!(r(24, 29, &mut rng), None);
+        assert_eq!(r(10, 10, &mut rng), None);
+        for _ in 0..100 {
+            let p = r(90, 110, &mut rng).unwrap();
+            assert!([97u32, 101, 103, 107, 109].contains(&p.to_u32_digits()[0]));
+        }
+
+        let lo = BigUint::from(10u32).pow(30);
+        let hi = &lo + 100000u32;
+        let p = gen_prime_in_range(&lo, &hi, &mut rng).unwrap();
+        assert!(lo <= p && p < hi && is_prime(&p));
+    }
+
+    #[test]
+    fn safe_primes() {
+        let mut rng = rand::thread_rng();
+        for &bits in [3, 4, 10, 128].iter() {
+            let p = gen_safe_prime(bits, &mut rng);
+            assert_eq!(p.bits(), bits as u64);
+            assert!(is_safe_prime(&p));
+        }
+        let q = gen_sophie_germain_prime(64, &mut rng);
+        assert!(is_prime(&q) && is_safe_prime(&((q << 1) + 1u32)));
+
+        let safe: Vec<u32> = (0..100).filter(|&p| is_safe_prime(&p.into())).collect();
+        assert_eq!(safe, [5, 7, 11, 23, 47, 59, 83]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no safe prime has fewer than 3 bits")]
+    fn safe_prime_too_few_bits() {
+        gen_safe_prime(2, &mut rand::thread_rng());
+    }
+
+    #[test]
+    fn next_and_prev() {
+        for n in 0..200u64 {
+            let next = (n + 1..).find(|&p| is_prime_u64(p));
+            let prev = (2..n).rev().find(|&p| is_prime_u64(p));
+            assert_eq!(next_prime_u64(n), next);
+            assert_eq!(prev_prime_u64(n), prev);
+            assert_eq!(Some(next_prime(&n.into())), next.map(BigUint::from));
+            assert_eq!(prev_prime(&n.into()), prev.map(BigUint::from));
+        }
+
+        // 2^64 - 59 is the largest 64-bit prime.
+        let top = u64::MAX - 58;
+        assert_eq!(prev_prime_u64(u64::MAX), Some(top));
+        assert_eq!(next_prime_u64(top - 1), Some(top));
+        assert_eq!(next_prime_u64(top), None);
+        assert_eq!(next_prime(&top.into()), BigUint::from(u64::MAX) + 14u32);
+
+        let m127 = (BigUint::from(1u32) << 127) - 1u32;
+        assert_eq!(next_prime(&(&m127 - 1u32)), m127);
+        assert_eq!(prev_prime(&(&m127 + 1u32)), Some(m127.clone()));
+        let p = next_prime(&m127);
+        assert!(is_prime(&p) && p > m127);
+        let gap = (&p - &m127).to_u32_digits()[0];
+        assert!((1..gap).all(|i| !is_prime(&(&m127 + i))));
+    }
+}
@@ -0,0 +1,542 @@
+// The prime counting function. Small x are counted with the sieve, mid-range
+// x with Meissel's formula and large x with Lagarias-Miller-Odlyzko as laid
+// out by Deleglise and Rivat.
+
+use crate::{
+    primality::{icbrt_u64, isqrt_u128},
+    sieve::{primes_in_range, primes_up_to},
+};
+
+const SIEVE_LIMIT: u64 = 1 << 16;
+const MEISSEL_LIMIT: u64 = 1 << 36;
+
+// phi(x, a) for a up to this many primes is read off a primorial period.
+const TINY_A: usize = 6;
+
+pub fn prime_pi(x: u64) -> u64 {
+    if x < SIEVE_LIMIT {
+        primes_in_range(0, x + 1).count() as u64
+    } else if x < MEISSEL_LIMIT {
+        meissel(x)
+    } else {
+        lmo(x)
+    }
+}
+
+// The n-th prime counting from nth_prime(1) = 2, or None if it is not below
+// 2^64. pi is evaluated at an estimate and the rest is sieved.
+pub fn nth_prime(n: u64) -> Option<u64> {
+    if n < 6 {
+        return [None, Some(2), Some(3), Some(5), Some(7), Some(11)][n as usize];
+    }
+    // p_n ~ n (ln n + ln ln n - 1 + (ln ln n - 2) / ln n).
+    let (f, ln) = (n as f64, (n as f64).ln());
+    let estimate = f * (ln + ln.ln() - 1.0 + (ln.ln() - 2.0) / ln);
+    let x = if estimate < u64::MAX as f64 {
+        estimate as u64
+    } else {
+        u64::MAX - 1
+    };
+    let count = prime_pi(x);
+    if count < n {
+        return primes_in_range(x + 1, u64::MAX).nth((n - count - 1) as usize);
+    }
+    // The (count - n)-th prime from the top among those up to x.
+    let mut k = (count - n) as usize;
+    let mut hi = x + 1;
+    loop {
+        let lo = hi.saturating_sub(1 << 20);
+        let window: Vec<u64> = primes_in_range(lo, hi).collect();
+        if k < window.len() {
+            return Some(window[window.len() - 1 - k]);
+        }
+        k -= window.len();
+        hi = lo;
+    }
+}
+
+fn isqrt(n: u64) -> u64 {
+    isqrt_u128(n as u128) as u64
+}
+
+// pi(n) for n up to a limit, as a bitmap of primes with counts per word.
+struct PiTable {
+    bits: Vec<u64>,
+    counts: Vec<u32>,
+}
+
+impl PiTable {
+    fn new(primes: &[u64], limit: u64) -> Self {
+        let words = (limit / 64 + 1) as usize;
+        let mut bits = vec![0u64; words];
+        for &p in primes.iter().take_while(|&&p| p <= limit) {
+            bits[(p / 64) as usize] |= 1 << (p % 64);
+        }
+        let mut counts = vec![0u32; words];
+        for i in 1..words {
+            counts[i] = counts[i - 1] + bits[i - 1].count_ones();
+        }
+        PiTable { bits, counts }
+    }
+
+    fn pi(&self, n: u64) -> u64 {
+        let i = (n / 64) as usize;
+        let mask = u64::MAX >> (63 - n % 64);
+        self.counts[i] as u64 + (self.bits[i] & mask).count_ones() as u64
+    }
+}
+
+// phi(x, a), the count of integers in [1, x] free of the first a primes, for
+// a <= TINY_A.
+struct TinyPhi {
+    tables: Vec<Vec<u32>>,
+}
+
+impl TinyPhi {
+    fn new(primes: &[u64]) -> Self {
+        let mut tables = vec![vec![0, 1]];
+        let mut period = 1;
+        for &p in primes.iter().take(TINY_A) {
+            period *= p as usize;
+            let table = (0..=period)
+                .map(|n| {
+                    let prev = &tables[tables.len() - 1];
+                    let phi = |n: usize| {
+                        let len = prev.len() - 1;
+                        (n / len) as u32 * prev[len] + prev[n % len]
+                    };
+                    phi(n) - phi(n / p as usize)
+                })
+                .collect();
+            tables.push(table);
+        }
+        TinyPhi { tables }
+    }
+
+    fn phi(&self, x: u64, a: usize) -> u64 {
+        let table = &self.tables[a];
+        let period = (table.len() - 1) as u64;
+        x / period * table[period as usize] as u64 + table[(x % period) as usize] as u64
+    }
+}
+
+// Meissel: pi(x) = phi(x, a) + a - 1 - P2(x, a) with a = pi(x^(1/3)), where
+// P2 counts the n <= x with exactly two prime factors above p_a.
+fn meissel(x: u64) -> u64 {
+    let x13 = icbrt_u64(x);
+    let limit = x / x13;
+    let primes = primes_up_to(limit);
+    let pi = PiTable::new(&primes, limit);
+    let tiny = TinyPhi::new(&primes);
+    let a = pi.pi(x13);
+    let b = pi.pi(isqrt(x));
+
+    let mut p2 = 0;
+    for (i, &p) in primes.iter().enumerate().take(b as usize).skip(a as usize) {
+        p2 += pi.pi(x / p) - i as u64;
+    }
+    phi(x, a as usize, &primes, &pi, limit, &tiny) + a - 1 - p2
+}
+
+fn phi(x: u64, a: usize, primes: &[u64], pi: &PiTable, limit: u64, tiny: &TinyPhi) -> u64 {
+    if a <= TINY_A {
+        return tiny.phi(x, a);
+    }
+    // Only 1 and the primes above p_a are left below p_(a+1)^2.
+    if x <= limit && x < primes[a] * primes[a] {
+        return (pi.pi(x) + 1).saturating_sub(a as u64).max(1);
+    }
+    let mut ret = tiny.phi(x, TINY_A);
+    for (i, &p) in primes.iter().enumerate().take(a).skip(TINY_A) {
+        if x / p < p {
+            // phi(x / p_j, j - 1) = 1 for each remaining p_j <= x.
+            ret -= pi.pi(x.min(primes[a - 1])) - i as u64;
+            break;
+        }
+        ret -= phi(x / p, i, primes, pi, limit, tiny);
+    }
+    ret
+}
+
+// The unsieved odd numbers of a segment as a bitmap, with a running count
+// per block so that prefix counts for increasing positions are cheap.
+struct Segment {
+    bits: Vec<u64>,
+    counters: Vec<u32>,
+    block_log: u32,
+    count: u64,
+    // Bit k is set when 2k + 1 is free of the odd primes among the first
+    // TINY_A, for k up to a period and a few words past it.
+    pattern: Vec<u64>,
+    period: usize,
+}
+
+// Prefix count state of one ascending sweep of a segment.
+#[derive(Default)]
+struct Sweep {
+    block: usize,
+    sum: u64,
+}
+
+impl Segment {
+    fn new(len: usize, block_log: u32, primes: &[u64]) -> Self {
+        let period: usize = primes[1..TINY_A].iter().product::<u64>() as usize;
+        let mut pattern = vec![0u64; period / 64 + 2];
+        for k in 0..pattern.len() * 64 {
+            if primes[1..TINY_A]
+                .iter()
+                .all(|&p| !(2 * k as u64 + 1).is_multiple_of(p))
+            {
+                pattern[k / 64] |= 1 << (k % 64);
+            }
+        }
+        Segment {
+            bits: vec![0; len / 128],
+            counters: vec![0; (len / 2) >> block_log],
+            block_log,
+            count: 0,
+            pattern,
+            period,
+        }
+    }
+
+    // Starts over from the odd numbers low + 1, low + 3, ... at len
+    // positions, already sieved by the odd primes up to primes[TINY_A - 1].
+    fn reset(&mut self, len: usize, low: u64) {
+        let mut k = (low / 2 % self.period as u64) as usize;
+        for (i, w) in self.bits.iter_mut().enumerate() {
+            let (j, r) = (k / 64, k % 64);
+            let word = if r == 0 {
+                self.pattern[j]
+            } else {
+                (self.pattern[j] >> r) | (self.pattern[j + 1] << (64 - r))
+            };
+            *w = word
+                & match len.saturating_sub(i * 64) {
+                    0 => 0,
+                    n if n >= 64 => u64::MAX,
+                    n => (1 << n) - 1,
+                };
+            k += 64;
+            if k >= self.period {
+                k -= self.period;
+            }
+        }
+        let words = 1 << (self.block_log - 6);
+        for (c, ws) in self.counters.iter_mut().zip(self.bits.chunks(words)) {
+            *c = ws.iter().map(|w| w.count_ones()).sum();
+        }
+        self.count = self.counters.iter().map(|&c| c as u64).sum();
+    }
+
+    fn remove(&mut self, i: usize) {
+        let w = &mut self.bits[i / 64];
+        let bit = (*w >> (i % 64)) & 1;
+        *w &= !(1 << (i % 64));
+        self.counters[i >> self.block_log] -= bit as u32;
+        self.count -= bit;
+    }
+
+    // Unsieved numbers at positions below i.
+    fn count_below(&self, sweep: &mut Sweep, i: usize) -> u64 {
+        let block = i >> self.block_log;
+        while sweep.block < block {
+            sweep.sum += self.counters[sweep.block] as u64;
+            sweep.block += 1;
+        }
+        let first = (block << self.block_log) / 64;
+        let mut ret = sweep.sum;
+        for &w in &self.bits[first..i / 64] {
+            ret += w.count_ones() as u64;
+        }
+        if !i.is_multiple_of(64) {
+            ret += (self.bits[i / 64] & (u64::MAX >> (64 - i % 64))).count_ones() as u64;
+        }
+        ret
+    }
+}
+
+// Primes in (lo, hi] from the top down, a window at a time.
+struct DescendingPrimes {
+    lo: u64,
+    hi: u64,
+    buf: Vec<u64>,
+}
+
+impl Iterator for DescendingPrimes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while self.buf.is_empty() && self.hi > self.lo {
+            let lo = self.hi.saturating_sub(1 << 20).max(self.lo);
+            self.buf = primes_in_range(lo + 1, self.hi + 1).collect();
+            self.hi = lo;
+        }
+        self.buf.pop()
+    }
+}
+
+// Lagarias-Miller-Odlyzko with y = alpha x^(1/3):
+//   phi(x, pi(y)) = sum_{m <= y} mu(m) floor(x / m)
+//     - sum_{p <= y} sum_{lpf(m) > p, m <= y < mp} mu(m) phi(x / mp, pi(p) - 1)
+// The special leaves phi(x / mp, pi(p) - 1) <= x / y are read off a
+// segmented sieve of the odd numbers up to x / y, which also counts the
+// primes for P2, except where pi(x / mp) or the tiny tables give them.
+fn lmo(x: u64) -> u64 {
+    let x13 = icbrt_u64(x);
+    let sqrt_x = isqrt(x);
+    let alpha = ((x as f64).ln().powi(2) / 100.0).max(1.0);
+    let y = ((x13 as f64 * alpha) as u64).clamp(x13, sqrt_x);
+    let z = x / y;
+
+    let primes = primes_up_to(y);
+    let pi = PiTable::new(&primes, y);
+    let pi_below = |n: u64| if n == 0 { 0 } else { pi.pi(n - 1) };
+    let a = primes.len();
+    let mu_lpf = mu_lpf(y as usize);
+
+    // Ordinary leaves, and the special leaves of p = 2 where phi(v, 0) = v.
+    let mut phi: i128 = 0;
+    for (m, &t) in mu_lpf.iter().enumerate().skip(1) {
+        phi += t.signum() as i128 * (x / m as u64) as i128;
+        if m as u64 > y / 2 && t.unsigned_abs() > 2 {
+            phi -= t.signum() as i128 * (x / (2 * m as u64)) as i128;
+        }
+    }
+
+    // For p = primes[b] <= sqrt(y), the leaves run over squarefree m from y
+    // down to y / p. Above sqrt(y) m must be a prime q > p, walked by index.
+    let pi_sqrt_y = pi.pi(isqrt(y)) as usize;
+    let mut cursor: Vec<u64> = (0..a)
+        .map(|b| if b < pi_sqrt_y { y } else { a as u64 - 1 })
+        .collect();
+    // Leaves with x / pq < p have phi = 1 and are counted in bulk.
+    for (b, &p) in primes.iter().enumerate().skip(pi_sqrt_y) {
+        let t = x / (p * p);
+        let first = if t >= y { a } else { pi.pi(t) as usize }.max(b + 1);
+        phi += (a - first) as i128;
+        cursor[b] = first as u64 - 1;
+    }
+    // Above sqrt(y) the leaves with x / pq <= y are easy, phi(x / pq, b) =
+    // pi(x / pq) - b + 1. Where q is large enough that every pi(x / pq) = l
+    // is taken by some q, they are added a value of l at a time.
+    for (b, &p) in primes.iter().enumerate().skip(pi_sqrt_y) {
+        let xp = x / p;
+        let mut hi = cursor[b] as usize + 1;
+        if hi <= b + 1 {
+            continue;
+        }
+        let dense = isqrt(xp).min(y);
+        let v = xp / primes[hi - 1];
+        if v > dense {
+            continue;
+        }
+        let l0 = pi.pi(v) as usize;
+        let l1 = pi.pi(dense).min(a as u64 - 1) as usize;
+        for (l, &q) in primes.iter().enumerate().take(l1 + 1).skip(l0) {
+            let lo = (pi.pi(xp / q) as usize).max(b + 1);
+            if lo < hi {
+                phi += ((hi - lo) * (l + 1 - b)) as i128;
+                hi = lo;
+            }
+        }
+        cursor[b] = hi as u64 - 1;
+    }
+    // phi(low - 1, b).
+    let mut carry = vec![0u64; a];
+
+    let len = (isqrt(z) + 1).next_power_of_two().max(1 << 18) as usize;
+    let block_log = (len.trailing_zeros() / 2).max(6);
+    let mut segment = Segment::new(len, block_log, &primes);
+    let tiny = TinyPhi::new(&primes);
+
+    let mut p2_primes = DescendingPrimes {
+        lo: y,
+        hi: sqrt_x,
+        buf: vec![],
+    }
+    .peekable();
+    let (mut p2, mut p2_count) = (0u128, 0u64);
+    let mut pi_low = 0;
+
+    // Segments [low, high) start even and keep their odd numbers, sieved by
+    // one odd prime after another.
+    let mut low = 0;
+    while low <= z {
+        let high = (low + len as u64).min(z + 1);
+        segment.reset((high / 2 - low / 2) as usize, low);
+        let odd_below = |v: u64| (v - low).div_ceil(2) as usize;
+        let leaf_end = pi_sqrt_y.max(pi.pi(isqrt(x / low.max(1)).min(y)) as usize);
+        let sieve_end = leaf_end.max(pi.pi(isqrt(high - 1)) as usize).max(TINY_A);
+
+        for (b, &p) in primes.iter().enumerate().take(sieve_end).skip(1) {
+            if b < leaf_end {
+                let mut sweep = Sweep::default();
+                let xp = x / p;
+                if b < pi_sqrt_y {
+                    let mut m = cursor[b];
+                    loop {
+                        while m > y / p && mu_lpf[m as usize].unsigned_abs() as u64 <= p {
+                            m -= 1;
+                        }
+                        let v = xp / m;
+                        if m <= y / p || v >= high {
+                            break;
+                        }
+                        let phi_v = if v < p * p {
+                            (pi.pi(v) + 1).saturating_sub(b as u64).max(1)
+                        } else if b < TINY_A {
+                            tiny.phi(v, b)
+                        } else {
+                            carry[b] + segment.count_below(&mut sweep, odd_below(v))
+                        };
+                        phi -= mu_lpf[m as usize].signum() as i128 * phi_v as i128;
+                        m -= 1;
+                    }
+                    cursor[b] = m;
+                } else {
+                    // m = q is prime, and p^2 > y, so the leaves up to y
+                    // are read off the table.
+                    let mut j = cursor[b] as usize;
+                    let mut sum = 0;
+                    while j > b {
+                        let v = xp / primes[j];
+                        if v >= high {
+                            break;
+                        }
+                        sum += if v <= y {
+                            pi.pi(v) + 1 - b as u64
+                        } else if b < TINY_A {
+                            tiny.phi(v, b)
+                        } else {
+                            carry[b] + segment.count_below(&mut sweep, odd_below(v))
+                        };
+                        j -= 1;
+                    }
+                    cursor[b] = j as u64;
+                    phi += sum as i128;
+                }
+                carry[b] += segment.count;
+            }
+            // The pattern took these out already.
+            if b < TINY_A {
+                continue;
+            }
+            let mut n = low.div_ceil(p) * p;
+            if n % 2 == 0 {
+                n += p;
+            }
+            while n < high {
+                segment.remove(((n - low) / 2) as usize);
+                n += 2 * p;
+            }
+        }
+
+        // What is left are 1 and the primes above the last sieving prime.
+        let pk = primes[sieve_end - 1];
+        let mut sweep = Sweep::default();
+        let primes_to = |sweep: &mut Sweep, t: u64| {
+            let small = if low <= pk {
+                pi.pi(t.min(pk)) - pi_below(low)
+            } else {
+                0
+            };
+            pi_low + segment.count_below(sweep, odd_below(t)) + small - (low == 0) as u64
+        };
+        while let Some(&p) = p2_primes.peek() {
+            let t = x / p;
+            if t >= high {
+                break;
+            }
+            p2 += primes_to(&mut sweep, t) as u128;
+            p2_count += 1;
+            p2_primes.next();
+        }
+        pi_low = primes_to(&mut sweep, high - 1);
+        low = high;
+    }
+
+    // P2 = sum_{y < p <= sqrt(x)} pi(x / p) - pi(p) + 1.
+    let n = p2_count as u128;
+    p2 -= n * a as u128 + n * n.saturating_sub(1) / 2;
+    (phi + a as i128 - 1 - p2 as i128) as u64
+}
+
+// mu(m) * lpf(m) for m <= n, with lpf(1) = i32::MAX.
+fn mu_lpf(n: usize) -> Vec<i32> {
+    let mut ret = vec![0i32; n + 1];
+    for p in 2..=n {
+        if ret[p] == 0 {
+            for m in (p..=n).step_by(p) {
+                if ret[m] == 0 {
+                    ret[m] = p as i32;
+                }
+            }
+        }
+    }
+    if n >= 1 {
+        ret[1] = i32::MAX;
+    }
+    for m in 2..=n {
+        let p = ret[m];
+        let q = ret[m / p as usize];
+        ret[m] = if q == 0 || q.abs() == p {
+            0
+        } else {
+            -q.signum() * p
+        };
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn prime_counts() {
+        let pi_10k = [
+            4,
+            25,
+            168,
+            1229,
+            9592,
+            78498,
+            664579,
+            5761455,
+            50847534,
+            455052511,
+            4118054813,
+            37607912018,
+            346065536839,
+            3204941750802,
+        ];
+        for (k, &expected) in pi_10k.iter().enumerate() {
+            assert_eq!(prime_pi(10u64.pow(k as u32 + 1)), expected);
+        }
+        assert_eq!(prime_pi(0), 0);
+        assert_eq!(prime_pi(2), 1);
+    }
+
+    #[test]
+    fn methods_agree() {
+        use super::{lmo, meissel};
+        for &x in [1 << 20, 12_345_678, 100_000_007].iter() {
+            let expected = primes_in_range(0, x + 1).count() as u64;
+            assert_eq!(meissel(x), expected);
+            assert_eq!(lmo(x), expected);
+        }
+        let x = 98_765_432_109;
+        assert_eq!(meissel(x), lmo(x));
+    }
+
+    #[test]
+    fn nth_primes() {
+        assert_eq!(nth_prime(0), None);
+        let first: Vec<u64> = (1..=30).map(|n| nth_prime(n).unwrap()).collect();
+        assert_eq!(first, primes().take(30).collect::<Vec<_>>());
+        assert_eq!(nth_prime(1000), Some(7919));
+        assert_eq!(nth_prime(1_000_000), Some(15485863));
+        assert_eq!(nth_prime(1_000_000_000), Some(22801763489));
+    }
+}
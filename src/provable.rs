@@ -0,0 +1,193 @@
+// Primes that are proven by construction: each is built as n = 2Rq + 1 from a
+// smaller proven prime q, and a Pocklington witness for q comes out of the
+// search, so the certificate chain is a by-product of generation.
+
+use num_bigint::{BigUint, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use rand::Rng;
+
+use crate::{
+    cert::factored_part_suffices, is_prime_u64, sha256::sha256, sieve::primes_up_to, Certificate,
+};
+
+// Below this many bits primes are found directly and certified by trial
+// division.
+const SMALL_BITS: usize = 32;
+
+// Maurer keeps the bit length of n - 1 beyond q at least this large, and
+// trial divides candidates up to MAURER_C * bits^2.
+const MAURER_MARGIN: usize = 20;
+const MAURER_C: f64 = 0.1;
+
+const SHA256_BITS: usize = 256;
+
+pub fn gen_maurer_prime(bits: usize, rng: &mut impl Rng) -> (BigUint, Certificate) {
+    assert!(bits >= 2, "no prime has fewer than 2 bits");
+    if bits <= SMALL_BITS {
+        loop {
+            let n = rng.gen_range(1u64 << (bits - 1), 1u64 << bits) | 1;
+            if is_prime_u64(n) {
+                return (n.into(), Certificate::Small(n));
+            }
+        }
+    }
+
+    // q has a random fraction r in [1/2, 1) of the bits, distributed like the
+    // relative size of the largest prime factor of a random integer.
+    let r = if bits > 2 * MAURER_MARGIN {
+        loop {
+            let r = 2f64.powf(rng.gen::<f64>() - 1.0);
+            if bits as f64 * (1.0 - r) > MAURER_MARGIN as f64 {
+                break r;
+            }
+        }
+    } else {
+        0.5
+    };
+    let (q, q_cert) = gen_maurer_prime((r * bits as f64) as usize + 1, rng);
+
+    let primes = primes_up_to((MAURER_C * (bits * bits) as f64) as u64);
+    let i = (BigUint::one() << (bits - 1)) / (&q << 1);
+    loop {
+        let r = rng.gen_biguint_range(&(&i + 1u32), &((&i << 1) + 1u32));
+        let n: BigUint = ((&r * &q) << 1) + 1u32;
+        if n.bits() != bits as u64 || primes.iter().any(|&p| (&n % p).is_zero()) {
+            continue;
+        }
+        let n1: BigUint = &n - 1u32;
+        let a = rng.gen_biguint_range(&2u32.into(), &n1);
+        if !a.modpow(&n1, &n).is_one() {
+            continue;
+        }
+        let z = a.modpow(&(&r << 1), &n);
+        if ((z + &n1) % &n).gcd(&n).is_one() && factored_part_suffices(&n, &q) {
+            let cert = Certificate::Pocklington {
+                n: n.clone(),
+                factors: vec![(q_cert, a)],
+            };
+            return (n, cert);
+        }
+    }
+}
+
+// The Shawe-Taylor random prime routine of FIPS 186-5 with SHA-256. The
+// result is a deterministic function of the seed, and None reports the
+// routine's failure status.
+pub fn shawe_taylor_prime(bits: usize, seed: &[u8]) -> Option<(BigUint, Certificate)> {
+    let mut st = ShaweTaylor {
+        prime_seed: BigUint::from_bytes_be(seed),
+        seed_len: seed.len(),
+        counter: 0,
+    };
+    st.random_prime(bits)
+}
+
+struct ShaweTaylor {
+    prime_seed: BigUint,
+    seed_len: usize,
+    counter: usize,
+}
+
+impl ShaweTaylor {
+    // Hash(prime_seed + i), with the seed kept as a seed_len byte string.
+    fn hash(&self, i: usize) -> BigUint {
+        let x = (&self.prime_seed + i) % (BigUint::one() << (8 * self.seed_len));
+        let bytes = x.to_bytes_be();
+        let mut input = vec![0u8; self.seed_len.saturating_sub(bytes.len())];
+        input.extend_from_slice(&bytes);
+        BigUint::from_bytes_be(&sha256(&input))
+    }
+
+    // Concatenates iterations + 1 hashes and advances the seed past them.
+    fn hash_blocks(&mut self, iterations: usize) -> BigUint {
+        let mut x = BigUint::default();
+        for i in 0..=iterations {
+            x += self.hash(i) << (i * SHA256_BITS);
+        }
+        self.prime_seed += iterations + 1;
+        x
+    }
+
+    fn random_prime(&mut self, length: usize) -> Option<(BigUint, Certificate)> {
+        if length < 2 {
+            return None;
+        }
+        let top = BigUint::one() << (length - 1);
+
+        if length <= SMALL_BITS {
+            loop {
+                let c: BigUint = self.hash(0) ^ self.hash(1);
+                let c: BigUint = ((&top + c % &top) >> 1 << 1) + 1u32;
+                self.counter += 1;
+                self.prime_seed += 2u32;
+                let c = c.to_u64().unwrap();
+                if is_prime_u64(c) {
+                    return Some((c.into(), Certificate::Small(c)));
+                }
+                if self.counter > 4 * length {
+                    return None;
+                }
+            }
+        }
+
+        let (c0, c0_cert) = self.random_prime(length.div_ceil(2) + 1)?;
+        let iterations = length.div_ceil(SHA256_BITS) - 1;
+        let old_counter = self.counter;
+        let x = &top + self.hash_blocks(iterations) % &top;
+        let two_c0: BigUint = &c0 << 1;
+        let mut t = x.div_ceil(&two_c0);
+        loop {
+            if &two_c0 * &t + 1u32 > BigUint::one() << length {
+                t = top.div_ceil(&two_c0);
+            }
+            let c: BigUint = &two_c0 * &t + 1u32;
+            self.counter += 1;
+
+            let a = self.hash_blocks(iterations) % (&c - 3u32) + 2u32;
+            let z = a.modpow(&(&t << 1), &c);
+            if ((&z + &c - 1u32) % &c).gcd(&c).is_one() && z.modpow(&c0, &c).is_one() {
+                let cert = Certificate::Pocklington {
+                    n: c.clone(),
+                    factors: vec![(c0_cert, a)],
+                };
+                return Some((c, cert));
+            }
+            if self.counter >= 4 * length + old_counter {
+                return None;
+            }
+            t += 1u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn maurer() {
+        let mut rng = rand::thread_rng();
+        for &bits in [2, 16, 33, 100, 300].iter() {
+            let (p, cert) = gen_maurer_prime(bits, &mut rng);
+            assert_eq!(p.bits(), bits as u64);
+            assert!(verify_certificate(&p, &cert));
+        }
+    }
+
+    #[test]
+    fn shawe_taylor() {
+        let seed = [0x5a; 32];
+        for &bits in [2, 32, 33, 256, 600].iter() {
+            let (p, cert) = shawe_taylor_prime(bits, &seed).unwrap();
+            assert_eq!(p.bits(), bits as u64);
+            assert!(verify_certificate(&p, &cert));
+            assert_eq!(shawe_taylor_prime(bits, &seed).unwrap().0, p);
+        }
+        assert_eq!(shawe_taylor_prime(1, &seed), None);
+        assert_ne!(
+            shawe_taylor_prime(256, &[1; 32]).unwrap().0,
+            shawe_taylor_prime(256, &[2; 32]).unwrap().0
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use num_bigint::{BigInt, BigUint, RandBigInt, Sign};
+use num_integer::Integer;
+use num_traits::One;
+use rand::Rng;
+
+use crate::{gen_prime_exact_bits, is_random_prime_candidate, mod_inverse, TopBits};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RsaOptions {
+    pub e: BigUint,
+    // Build p and q from auxiliary primes r1 | p - 1 and r2 | p + 1 as in
+    // FIPS 186-5 A.1.5.
+    pub auxiliary_primes: bool,
+}
+
+impl Default for RsaOptions {
+    fn default() -> Self {
+        RsaOptions {
+            e: 65537u32.into(),
+            auxiliary_primes: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RsaPrivateKey {
+    pub n: BigUint,
+    pub e: BigUint,
+    pub d: BigUint,
+    pub p: BigUint,
+    pub q: BigUint,
+    pub dp: BigUint,
+    pub dq: BigUint,
+    pub qinv: BigUint,
+}
+
+pub fn gen_rsa_key(bits: usize, rng: &mut impl Rng) -> RsaPrivateKey {
+    gen_rsa_key_with(bits, &RsaOptions::default(), rng)
+}
+
+pub fn gen_rsa_key_with(bits: usize, options: &RsaOptions, rng: &mut impl Rng) -> RsaPrivateKey {
+    let e = &options.e;
+    assert!(
+        e.is_odd() && e > &BigUint::one(),
+        "e must be odd and above 1"
+    );
+    let (p_bits, q_bits) = (bits.div_ceil(2), bits / 2);
+    let gen = |bits: usize, rng: &mut _| {
+        if options.auxiliary_primes {
+            gen_prime_with_auxiliary_primes(bits, e, rng)
+        } else {
+            loop {
+                let p = gen_prime_exact_bits(bits, TopBits::Two, rng);
+                if (&p - 1u32).gcd(e).is_one() {
+                    break p;
+                }
+            }
+        }
+    };
+
+    // FIPS 186-5 asks for |p - q| > 2^(nlen/2 - 100) and d > 2^(nlen/2).
+    let min_distance = BigUint::one() << (bits / 2).saturating_sub(100);
+    let min_d = BigUint::one() << (bits / 2);
+    loop {
+        let p = gen(p_bits, rng);
+        let q = gen(q_bits, rng);
+        let distance = if p > q { &p - &q } else { &q - &p };
+        if distance <= min_distance {
+            continue;
+        }
+        if let Some(key) = RsaPrivateKey::from_primes(p, q, e.clone()) {
+            if key.n.bits() == bits as u64 && key.d > min_d {
+                return key;
+            }
+        }
+    }
+}
+
+// Lengths of the auxiliary primes, from FIPS 186-5 table A.1 and scaled down
+// for toy key sizes.
+fn auxiliary_prime_bits(bits: usize) -> usize {
+    let min = match bits * 2 {
+        n if n >= 4096 => 200,
+        n if n >= 3072 => 170,
+        _ => 140,
+    };
+    min.min(bits / 4).max(2)
+}
+
+// FIPS 186-5 B.9: a prime p with r1 | p - 1 and r2 | p + 1.
+fn gen_prime_with_auxiliary_primes(bits: usize, e: &BigUint, rng: &mut impl Rng) -> BigUint {
+    let aux_bits = auxiliary_prime_bits(bits);
+    let lo = BigUint::from(3u32) << (bits - 2);
+    let hi = BigUint::one() << bits;
+    loop {
+        let r1 = gen_prime_exact_bits(aux_bits, TopBits::One, rng);
+        let r2 = gen_prime_exact_bits(aux_bits + 1, TopBits::One, rng);
+        let r1_2: BigUint = &r1 << 1;
+        let (inv1, inv2) = match (mod_inverse(&r2, &r1_2), mod_inverse(&r1_2, &r2)) {
+            (Ok(a), Ok(b)) => (a, b),
+            _ => continue,
+        };
+
+        // R = 1 mod 2 r1 and R = -1 mod r2.
+        let m: BigUint = &r1_2 * &r2;
+        let r = BigInt::from_biguint(Sign::Plus, inv1 * &r2)
+            - BigInt::from_biguint(Sign::Plus, inv2 * &r1_2);
+        let r = r
+            .mod_floor(&BigInt::from_biguint(Sign::Plus, m.clone()))
+            .to_biguint()
+            .unwrap();
+
+        let x = rng.gen_biguint_range(&lo, &hi);
+        let mut y = &x + (&r + &m - &x % &m) % &m;
+        for _ in 0..5 * bits {
+            if y >= hi {
+                break;
+            }
+            if (&y - 1u32).gcd(e).is_one() && is_random_prime_candidate(&y, rng) {
+                return y;
+            }
+            y += &m;
+        }
+    }
+}
+
+impl RsaPrivateKey {
+    // None if p = q, e is not invertible modulo lambda(n), or p, q < 3.
+    pub fn from_primes(p: BigUint, q: BigUint, e: BigUint) -> Option<Self> {
+        let three = BigUint::from(3u32);
+        if p == q || p < three || q < three {
+            return None;
+        }
+        let (p1, q1) = (&p - 1u32, &q - 1u32);
+        let lambda = p1.lcm(&q1);
+        let d = mod_inverse(&e, &lambda).ok()?;
+        Some(RsaPrivateKey {
+            n: &p * &q,
+            dp: &d % &p1,
+            dq: &d % &q1,
+            qinv: mod_inverse(&q, &p).ok()?,
+            e,
+            d,
+            p,
+            q,
+        })
+    }
+
+    pub fn encrypt(&self, m: &BigUint) -> BigUint {
+        m.modpow(&self.e, &self.n)
+    }
+
+    // CRT decryption.
+    pub fn decrypt(&self, c: &BigUint) -> BigUint {
+        let m1 = c.modpow(&self.dp, &self.p);
+        let m2 = c.modpow(&self.dq, &self.q);
+        let h = &self.qinv * ((m1 + &self.p - &m2 % &self.p) % &self.p) % &self.p;
+        m2 + h * &self.q
+    }
+
+    // PKCS#1 RSAPrivateKey (two-prime, version 0).
+    pub fn to_pkcs1_der(&self) -> Vec<u8> {
+        let mut body = der_integer(&BigUint::default());
+        for x in [
+            &self.n, &self.e, &self.d, &self.p, &self.q, &self.dp, &self.dq, &self.qinv,
+        ]
+        .iter()
+        {
+            body.extend(der_integer(x));
+        }
+        der_sequence(body)
+    }
+
+    // PKCS#1 RSAPublicKey.
+    pub fn public_key_to_pkcs1_der(&self) -> Vec<u8> {
+        let mut body = der_integer(&self.n);
+        body.extend(der_integer(&self.e));
+        der_sequence(body)
+    }
+}
+
+fn der_length(len: usize) -> Vec<u8> {
+    if len < 0x80 {
+        return vec![len as u8];
+    }
+    let bytes: Vec<u8> = len
+        .to_be_bytes()
+        .iter()
+        .copied()
+        .skip_while(|&b| b == 0)
+        .collect();
+    let mut ret = vec![0x80 | bytes.len() as u8];
+    ret.extend(bytes);
+    ret
+}
+
+fn der_integer(x: &BigUint) -> Vec<u8> {
+    let mut content = x.to_bytes_be();
+    // Non-negative integers get a leading zero when the top bit is set.
+    if content[0] & 0x80 != 0 {
+        content.insert(0, 0);
+    }
+    let mut ret = vec![0x02];
+    ret.extend(der_length(content.len()));
+    ret.extend(content);
+    ret
+}
+
+fn der_sequence(body: Vec<u8>) -> Vec<u8> {
+    let mut ret = vec![0x30];
+    ret.extend(der_length(body.len()));
+    ret.extend(body);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn small_key() {
+        let key = RsaPrivateKey::from_primes(61u32.into(), 53u32.into(), 17u32.into()).unwrap();
+        assert_eq!(key.n, BigUint::from(3233u32));
+        assert_eq!(key.d, BigUint::from(413u32));
+        assert_eq!(
+            key.public_key_to_pkcs1_der(),
+            [0x30, 0x07, 0x02, 0x02, 0x0c, 0xa1, 0x02, 0x01, 0x11]
+        );
+        let der = key.to_pkcs1_der();
+        assert_eq!(&der[..5], [0x30, 0x1d, 0x02, 0x01, 0x00]);
+        assert_eq!(key.qinv, BigUint::from(38u32));
+        assert_eq!(&der[der.len() - 3..], [0x02, 0x01, 0x26]);
+        assert_eq!(
+            RsaPrivateKey::from_primes(61u32.into(), 61u32.into(), 17u32.into()),
+            None
+        );
+        assert_eq!(
+            RsaPrivateKey::from_primes(61u32.into(), 53u32.into(), 3u32.into()),
+            None
+        );
+    }
+
+    #[test]
+    fn generated_keys() {
+        let mut rng = rand::thread_rng();
+        let strong = RsaOptions {
+            auxiliary_primes: true,
+            ..RsaOptions::default()
+        };
+        for (bits, key) in [
+            (512, gen_rsa_key(512, &mut rng)),
+            (511, gen_rsa_key(511, &mut rng)),
+            (512, gen_rsa_key_with(512, &strong, &mut rng)),
+        ]
+        .iter()
+        {
+            assert_eq!(key.n.bits(), *bits);
+            assert_eq!(key.n, &key.p * &key.q);
+            let m = BigUint::from(0xdeadbeefu32);
+            assert_eq!(key.decrypt(&key.encrypt(&m)), m);
+            assert_eq!(key.to_pkcs1_der()[..2], [0x30, 0x82]);
+        }
+    }
+}
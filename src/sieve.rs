@@ -0,0 +1,207 @@
+use crate::primality::isqrt_u128;
+
+// A segment holds the 8 residues coprime to 30 of 30 * SEGMENT_BYTES numbers,
+// one bit each, so that it stays in L1.
+const SEGMENT_BYTES: usize = 1 << 15;
+const WHEEL: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+const WHEEL_PRIMES: [u64; 3] = [2, 3, 5];
+
+// Bit of each residue coprime to 30.
+const WHEEL_BITS: [u8; 30] = {
+    let mut bits = [0; 30];
+    let mut i = 0;
+    while i < 8 {
+        bits[WHEEL[i] as usize] = 1 << i;
+        i += 1;
+    }
+    bits
+};
+
+pub(crate) fn primes_up_to(n: u64) -> Vec<u64> {
+    primes_in_range(0, n.saturating_add(1)).collect()
+}
+
+pub fn primes() -> Primes {
+    primes_in_range(0, u64::MAX)
+}
+
+// Primes in [lo, hi) in increasing order. Memory is the segment plus state for
+// the sieving primes up to sqrt(hi).
+pub fn primes_in_range(lo: u64, hi: u64) -> Primes {
+    Primes {
+        lo,
+        hi,
+        small: 0,
+        next_base: if lo < hi {
+            (lo / 30 * 30) as u128
+        } else {
+            hi as u128
+        },
+        base: 0,
+        segment: vec![],
+        pos: 0,
+        bits: 0,
+        sieving: vec![],
+        source: None,
+        pending: None,
+    }
+}
+
+pub struct Primes {
+    lo: u64,
+    hi: u64,
+    small: usize,
+    // Numbers at byte 0 of the next and the current segment.
+    next_base: u128,
+    base: u128,
+    segment: Vec<u8>,
+    pos: usize,
+    // Unmarked bits of segment[pos - 1] not yet yielded.
+    bits: u8,
+    sieving: Vec<SievingPrime>,
+    // Sieving primes from 7 up to sqrt(hi), enumerated lazily by a smaller
+    // sieve, and the first one not yet in use.
+    source: Option<Box<Primes>>,
+    pending: Option<u64>,
+}
+
+struct SievingPrime {
+    p: u64,
+    // Byte offset in the current segment of the next multiple p * m for each
+    // residue class m = WHEEL[k] (mod 30). Each class repeats every p bytes.
+    next: [u64; 8],
+}
+
+impl SievingPrime {
+    fn new(p: u64, base: u128) -> Self {
+        let p128 = p as u128;
+        let m0 = p128.max(base.div_ceil(p128));
+        let mut next = [0; 8];
+        for (x, &r) in next.iter_mut().zip(WHEEL.iter()) {
+            let m = m0 + (r as u128 + 30 - m0 % 30) % 30;
+            *x = ((p128 * m - base) / 30) as u64;
+        }
+        SievingPrime { p, next }
+    }
+}
+
+impl Primes {
+    // The next source prime p with p^2 < end, if any.
+    fn next_sieving_prime(&mut self, end: u128) -> Option<u64> {
+        // Multiples of 2, 3 and 5 are off the wheel already.
+        if end <= 49 {
+            return None;
+        }
+        let p = match self.pending.take() {
+            Some(p) => p,
+            None => {
+                let sqrt = isqrt_u128(self.hi as u128 - 1) as u64;
+                self.source
+                    .get_or_insert_with(|| Box::new(primes_in_range(7, sqrt + 1)))
+                    .next()?
+            }
+        };
+        if (p as u128) * (p as u128) >= end {
+            self.pending = Some(p);
+            return None;
+        }
+        Some(p)
+    }
+
+    fn sieve_segment(&mut self) {
+        let base = self.next_base;
+        let hi = self.hi as u128;
+        let len = ((hi - base).div_ceil(30) as usize).min(SEGMENT_BYTES);
+        let end = (base + 30 * len as u128).min(hi);
+        self.base = base;
+        self.next_base = base + 30 * len as u128;
+
+        while let Some(p) = self.next_sieving_prime(end) {
+            self.sieving.push(SievingPrime::new(p, base));
+        }
+
+        self.segment.clear();
+        self.segment.resize(len, 0);
+        if base == 0 {
+            self.segment[0] = 1;
+        }
+        let segment = &mut self.segment;
+        for sp in self.sieving.iter_mut() {
+            for (next, &r) in sp.next.iter_mut().zip(WHEEL.iter()) {
+                let mask = WHEEL_BITS[(sp.p * r % 30) as usize];
+                let mut i = *next as usize;
+                while i < len {
+                    segment[i] |= mask;
+                    i += sp.p as usize;
+                }
+                *next = (i - len) as u64;
+            }
+        }
+        self.pos = 0;
+    }
+}
+
+impl Iterator for Primes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while self.small < WHEEL_PRIMES.len() {
+            let p = WHEEL_PRIMES[self.small];
+            self.small += 1;
+            if self.lo <= p && p < self.hi {
+                return Some(p);
+            }
+        }
+        loop {
+            while self.bits == 0 {
+                if self.pos == self.segment.len() {
+                    if self.next_base >= self.hi as u128 {
+                        return None;
+                    }
+                    self.sieve_segment();
+                }
+                self.bits = !self.segment[self.pos];
+                self.pos += 1;
+            }
+            let k = self.bits.trailing_zeros() as usize;
+            self.bits &= self.bits - 1;
+            let n = self.base + 30 * (self.pos - 1) as u128 + WHEEL[k] as u128;
+            if n >= self.hi as u128 {
+                self.next_base = self.hi as u128;
+                self.pos = self.segment.len();
+                self.bits = 0;
+                return None;
+            }
+            if n >= self.lo as u128 {
+                return Some(n as u64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn segmented_sieve() {
+        assert_eq!(
+            primes().take(12).collect::<Vec<_>>(),
+            [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]
+        );
+        assert_eq!(primes_in_range(0, 10_000_000).count(), 664579);
+        assert_eq!(primes_in_range(0, 2).count(), 0);
+        assert_eq!(primes_in_range(3, 3).count(), 0);
+        assert_eq!(primes_in_range(5, 3).count(), 0);
+        assert_eq!(
+            primes_in_range(3, 30).collect::<Vec<_>>(),
+            [3, 5, 7, 11, 13, 17, 19, 23, 29]
+        );
+
+        // Ranges across segment boundaries and far from 0.
+        for &(lo, hi) in [(983_000, 984_200), (1 << 40, (1 << 40) + 100_000)].iter() {
+            let expected: Vec<u64> = (lo..hi).filter(|&n| is_prime_u64(n)).collect();
+            assert_eq!(primes_in_range(lo, hi).collect::<Vec<_>>(), expected);
+        }
+    }
+}
@@ -0,0 +1,788 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+};
+
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use rand::Rng;
+
+use crate::{ecpp::sqrt_mod, primality::jacobi_u128, sieve::primes_up_to, Montgomery64};
+
+// (max decimal digits, factor base size, sieve half-width M)
+const PARAMS: [(usize, usize, usize); 17] = [
+    (20, 100, 16384),
+    (25, 150, 16384),
+    (30, 200, 32768),
+    (35, 300, 32768),
+    (40, 450, 65536),
+    (45, 700, 65536),
+    (50, 1400, 65536),
+    (55, 2200, 98304),
+    (60, 3200, 98304),
+    (65, 4500, 131072),
+    (70, 6000, 131072),
+    (75, 9000, 131072),
+    (80, 14000, 131072),
+    (85, 17000, 163840),
+    (90, 20000, 196608),
+    (95, 23000, 229376),
+    (100, 26000, 262144),
+];
+
+const MULTIPLIERS: [u64; 30] = [
+    1, 2, 3, 5, 6, 7, 10, 11, 13, 14, 15, 17, 19, 21, 22, 23, 26, 29, 30, 31, 33, 34, 35, 37, 38,
+    39, 41, 42, 43, 46,
+];
+
+// Primes below this bound are not sieved; their contribution is folded
+// into the threshold instead (small prime variation).
+const SMALL_PRIME_SKIP: u32 = 32;
+
+// Bits subtracted from the sieve threshold to make up for the unsieved
+// small primes and prime powers, and for values well below the maximum.
+const THRESHOLD_FUDGE: f64 = 16.0;
+
+// Partial relations may carry one prime up to this multiple of the
+// largest factor base prime (large prime variation).
+const LARGE_PRIME_MULTIPLIER: u64 = 128;
+
+const EXTRA_RELATIONS: usize = 32;
+
+// [-M, M) is sieved a block of this many bytes at a time, small enough to
+// stay in L1. Every 2M in PARAMS is a multiple of it.
+const SIEVE_BLOCK: usize = 1 << 15;
+
+// n is trial divided up to this bound first.
+const TRIAL_BOUND: u64 = 1000;
+
+// Knuth–Schroeppel: pick k so that kn has many small quadratic residues.
+fn choose_multiplier(n: &BigUint) -> u64 {
+    let primes = primes_up_to(2000);
+    let mut best = (f64::MIN, 1);
+    for &k in MULTIPLIERS.iter() {
+        let kn = n * k;
+        let mut score = -0.5 * (k as f64).ln();
+        score += match (&kn % 8u32).to_u32().unwrap() {
+            1 => 2.0 * 2f64.ln(),
+            5 => 2f64.ln(),
+            3 | 7 => 0.5 * 2f64.ln(),
+            _ => 0.0,
+        };
+        for &p in primes.iter().skip(1) {
+            let r = (&kn % p).to_u64().unwrap();
+            let lp = (p as f64).ln();
+            if k.is_multiple_of(p) {
+                score += lp / p as f64;
+            } else if jacobi_u128(r as u128, p as u128) == 1 {
+                score += 2.0 * lp / (p - 1) as f64;
+            }
+        }
+        if score > best.0 {
+            best = (score, k);
+        }
+    }
+    best.1
+}
+
+struct FactorBase {
+    primes: Vec<u32>,
+    sqrt: Vec<u32>,
+    logp: Vec<u8>,
+    // Montgomery contexts for inverses mod each odd prime.
+    mont: Vec<Montgomery64>,
+}
+
+impl FactorBase {
+    // Fails with a prime factor of n if one turns up in the factor base.
+    fn new(n: &BigUint, kn: &BigUint, size: usize) -> Result<FactorBase, BigUint> {
+        let mut fb = FactorBase {
+            primes: vec![2],
+            sqrt: vec![1],
+            logp: vec![1],
+            mont: vec![Montgomery64::new(1)],
+        };
+        let mut limit = (size as u64 * 30).max(1000);
+        loop {
+            for p in primes_up_to(limit).into_iter().skip(1) {
+                if (p as u32) <= *fb.primes.last().unwrap() {
+                    continue;
+                }
+                if fb.primes.len() >= size {
+                    return Ok(fb);
+                }
+                if (n % p).is_zero() {
+                    return Err(p.into());
+                }
+                let r = (kn % p).to_u64().unwrap();
+                if r == 0 || jacobi_u128(r as u128, p as u128) == 1 {
+                    let t = sqrt_mod(&r.into(), &p.into()).unwrap();
+                    fb.primes.push(p as u32);
+                    fb.sqrt.push(t.to_u32().unwrap());
+                    fb.logp.push((p as f64).log2().round() as u8);
+                    fb.mont.push(Montgomery64::new(p));
+                }
+            }
+            limit *= 2;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.primes.len()
+    }
+
+    // a^-1 mod the i-th prime, for i > 0 and a not divisible by it.
+    fn inverse(&self, i: usize, a: u64) -> u64 {
+        let m = &self.mont[i];
+        let p = m.modulus();
+        m.from_montgomery(m.pow(m.to_montgomery(a), p - 2))
+    }
+}
+
+struct Relation {
+    y: BigUint,
+    // Matrix columns: 0 is the sign, i + 1 is the i-th factor base prime.
+    factors: Vec<usize>,
+    sqrt_extra: BigUint,
+}
+
+struct Polynomial {
+    a: BigInt,
+    b: BigInt,
+    c: BigInt,
+    a_idx: Vec<usize>,
+}
+
+// Chooses a = q_1 ... q_s close to target from mid-sized factor base primes.
+fn choose_a(
+    fb: &FactorBase,
+    target: &BigUint,
+    used: &HashSet<BigUint>,
+    rng: &mut impl Rng,
+) -> Option<(BigUint, Vec<usize>)> {
+    let ln_target = target.to_f64()?.ln();
+    let pmax = *fb.primes.last().unwrap() as f64;
+    let preferred = (pmax / 2.0).min(2000.0);
+    let s = (ln_target / preferred.ln()).ceil().max(1.0) as usize;
+    let size = (ln_target / s as f64).exp();
+
+    // Only primes from SMALL_PRIME_SKIP up are drawn, widening the window
+    // when it has too few of them.
+    let lo = fb
+        .primes
+        .partition_point(|&p| (p as f64) < size / 2.0 || p < SMALL_PRIME_SKIP);
+    let hi = fb
+        .primes
+        .partition_point(|&p| (p as f64) < size * 2.0)
+        .max(lo + s + 2)
+        .min(fb.len());
+    if s > 1 && hi < lo + s + 2 {
+        return None;
+    }
+
+    for _ in 0..100 {
+        let mut a_idx: Vec<usize> = vec![];
+        let mut a = BigUint::one();
+        while a_idx.len() + 1 < s {
+            let i = rng.gen_range(lo, hi);
+            if !a_idx.contains(&i) {
+                a_idx.push(i);
+                a *= fb.primes[i];
+            }
+        }
+
+        // The last prime is the one nearest target / a that gives an unused
+        // a, walking outwards. With s = 1 this is the only choice there is.
+        let rest = (target / &a).to_u64().unwrap_or(u64::MAX);
+        let i = fb.primes.partition_point(|&p| (p as u64) < rest);
+        let (mut below, mut above) = (i, i);
+        while below > 0 || above < fb.len() {
+            let j = if above == fb.len()
+                || (below > 0
+                    && rest - (fb.primes[below - 1] as u64) < fb.primes[above] as u64 - rest)
+            {
+                below -= 1;
+                below
+            } else {
+                above += 1;
+                above - 1
+            };
+            if fb.primes[j] < SMALL_PRIME_SKIP || a_idx.contains(&j) {
+                continue;
+            }
+            let a = &a * fb.primes[j];
+            if !used.contains(&a) {
+                a_idx.push(j);
+                a_idx.sort_unstable();
+                return Some((a, a_idx));
+            }
+        }
+        if s == 1 {
+            break;
+        }
+    }
+    None
+}
+
+fn find_dependencies(relations: &[Relation], ncols: usize) -> Vec<Vec<usize>> {
+    let mut weight = vec![0usize; ncols];
+    let mut odd_cols: Vec<Vec<usize>> = relations
+        .iter()
+        .map(|r| {
+            let mut cols = r.factors.clone();
+            cols.sort_unstable();
+            let mut odd = vec![];
+            for chunk in cols.chunk_by(|a, b| a == b) {
+                if chunk.len() % 2 == 1 {
+                    odd.push(chunk[0]);
+                }
+            }
+            odd
+        })
+        .collect();
+    for cols in odd_cols.iter() {
+        for &c in cols.iter() {
+            weight[c] += 1;
+        }
+    }
+
+    // Structured elimination: a row holding the only entry of some column
+    // can never be part of a dependency.
+    let mut alive = vec![true; relations.len()];
+    loop {
+        let mut changed = false;
+        for (i, cols) in odd_cols.iter().enumerate() {
+            if alive[i] && cols.iter().any(|&c| weight[c] == 1) {
+                alive[i] = false;
+                changed = true;
+                for &c in cols.iter() {
+                    weight[c] -= 1;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let rows: Vec<usize> = (0..relations.len()).filter(|&i| alive[i]).collect();
+    let mut col_index = vec![usize::MAX; ncols];
+    let mut live_cols = 0;
+    for c in 0..ncols {
+        if weight[c] > 0 {
+            col_index[c] = live_cols;
+            live_cols += 1;
+        }
+    }
+
+    let words = (live_cols + rows.len()).div_ceil(64);
+    let mut matrix: Vec<Vec<u64>> = rows
+        .iter()
+        .enumerate()
+        .map(|(k, &i)| {
+            let mut row = vec![0u64; words];
+            for &c in std::mem::take(&mut odd_cols[i]).iter() {
+                let c = col_index[c];
+                row[c / 64] ^= 1 << (c % 64);
+            }
+            let h = live_cols + k;
+            row[h / 64] |= 1 << (h % 64);
+            row
+        })
+        .collect();
+
+    let mut pivot_used = vec![false; rows.len()];
+    for c in 0..live_cols {
+        let (w, bit) = (c / 64, 1u64 << (c % 64));
+        let pivot = match (0..rows.len()).find(|&r| !pivot_used[r] && matrix[r][w] & bit != 0) {
+            Some(r) => r,
+            None => continue,
+        };
+        pivot_used[pivot] = true;
+        let pivot_row = std::mem::take(&mut matrix[pivot]);
+        for (r, row) in matrix.iter_mut().enumerate() {
+            if r != pivot && row[w] & bit != 0 {
+                for (x, y) in row.iter_mut().zip(pivot_row.iter()) {
+                    *x ^= y;
+                }
+            }
+        }
+        matrix[pivot] = pivot_row;
+    }
+
+    (0..rows.len())
+        .filter(|&r| !pivot_used[r])
+        .map(|r| {
+            (0..rows.len())
+                .filter(|&k| {
+                    let h = live_cols + k;
+                    matrix[r][h / 64] >> (h % 64) & 1 == 1
+                })
+                .map(|k| rows[k])
+                .collect()
+        })
+        .collect()
+}
+
+fn combine(n: &BigUint, fb: &FactorBase, relations: &[Relation], dep: &[usize]) -> Option<BigUint> {
+    let mut x = BigUint::one();
+    let mut y = BigUint::one();
+    let mut counts = vec![0u32; fb.len() + 1];
+    for &i in dep.iter() {
+        let r = &relations[i];
+        x = x * &r.y % n;
+        y = y * &r.sqrt_extra % n;
+        for &c in r.factors.iter() {
+            counts[c] += 1;
+        }
+    }
+    for (c, &e) in counts.iter().enumerate().skip(1) {
+        debug_assert!(e % 2 == 0);
+        if e > 0 {
+            y = y * BigUint::from(fb.primes[c - 1]).modpow(&(e / 2).into(), n) % n;
+        }
+    }
+    let g = (x + n - y).gcd(n);
+    if !g.is_one() && &g != n {
+        Some(g)
+    } else {
+        None
+    }
+}
+
+pub fn siqs(n: &BigUint) -> Option<BigUint> {
+    siqs_with_rng(n, &mut rand::thread_rng())
+}
+
+pub fn siqs_with_rng(n: &BigUint, rng: &mut impl Rng) -> Option<BigUint> {
+    if n <= &BigUint::from(3u32) {
+        return None;
+    }
+    if !n.bit(0) {
+        return Some(2u32.into());
+    }
+    let r = n.sqrt();
+    if &(&r * &r) == n {
+        return Some(r);
+    }
+    // Small factors are left to trial division, which also makes every
+    // multiplier coprime to n and rules out small primes n.
+    for p in primes_up_to(TRIAL_BOUND) {
+        if (n % p).is_zero() {
+            return if n == &p.into() { None } else { Some(p.into()) };
+        }
+    }
+    if n < &BigUint::from(TRIAL_BOUND * TRIAL_BOUND) {
+        return None;
+    }
+
+    let digits = n.to_string().len();
+    let &(_, fb_size, m) = PARAMS
+        .iter()
+        .find(|&&(d, _, _)| digits <= d)
+        .unwrap_or(&PARAMS[PARAMS.len() - 1]);
+
+    let k = choose_multiplier(n);
+    let kn = n * k;
+    let fb = match FactorBase::new(n, &kn, fb_size) {
+        Ok(fb) => fb,
+        Err(p) => return Some(p),
+    };
+    let kn_int = BigInt::from_biguint(Sign::Plus, kn.clone());
+
+    let pmax = *fb.primes.last().unwrap() as u64;
+    let large_bound = (pmax * LARGE_PRIME_MULTIPLIER).min(pmax * pmax);
+    let target = (&kn * 2u32).sqrt() / m;
+    let log_g_max = (m as f64).log2() + kn.bits() as f64 / 2.0 - 0.5;
+    let threshold = (log_g_max - (large_bound as f64).log2() - THRESHOLD_FUDGE).max(0.0) as u8;
+
+    let mut relations: Vec<Relation> = vec![];
+    let mut partials: HashMap<u64, Relation> = HashMap::new();
+    let mut used_a = HashSet::new();
+    let wanted = fb.len() + 1 + EXTRA_RELATIONS;
+
+    let mut sp = SievePrimes::default();
+    let mut sieve = vec![0u8; SIEVE_BLOCK];
+    let mut candidates = vec![];
+    // Sieve values start from init, so that every candidate has its top bit
+    // set and the scan can skip eight bytes at a time.
+    let init = 128u8.saturating_sub(threshold);
+
+    while relations.len() < wanted {
+        let (a, a_idx) = choose_a(&fb, &target, &used_a, rng)?;
+        used_a.insert(a.clone());
+
+        // b_l = (a / q_l) * gamma with gamma = t_l * (a / q_l)^-1 mod q_l.
+        let bs: Vec<BigUint> = a_idx
+            .iter()
+            .map(|&i| {
+                let q = fb.primes[i] as u64;
+                let a_l = &a / q;
+                let inv = fb.inverse(i, (&a_l % q).to_u64().unwrap());
+                let mut gamma = fb.sqrt[i] as u64 * inv % q;
+                if gamma > q / 2 {
+                    gamma = q - gamma;
+                }
+                a_l * gamma
+            })
+            .collect();
+        let b: BigUint = bs.iter().sum();
+        sp.reset(&fb, &a_idx, &a, &b, &bs, m);
+
+        let mut poly = Polynomial {
+            a: BigInt::from_biguint(Sign::Plus, a),
+            b: BigInt::from_biguint(Sign::Plus, b),
+            c: BigInt::zero(),
+            a_idx,
+        };
+
+        for index in 0..1usize << (bs.len() - 1) {
+            if index > 0 {
+                // Gray code step: b += 2 * e * B_j.
+                let j = index.trailing_zeros() as usize;
+                let negative = index.div_ceil(2 << j) % 2 == 1;
+                sp.next_b(j, negative);
+                let delta = BigInt::from_biguint(Sign::Plus, &bs[j] * 2u32);
+                if negative {
+                    poly.b -= delta;
+                } else {
+                    poly.b += delta;
+                }
+            }
+            poly.c = (&poly.b * &poly.b - &kn_int) / &poly.a;
+            sp.start_roots();
+
+            for block in 0..2 * m / SIEVE_BLOCK {
+                sp.sieve_block(&mut sieve, block, init);
+                let start = block * SIEVE_BLOCK;
+                for (w, chunk) in sieve.chunks_exact(8).enumerate() {
+                    let word = u64::from_ne_bytes(chunk.try_into().unwrap());
+                    if word & 0x8080_8080_8080_8080 != 0 {
+                        candidates.extend(
+                            (0..8)
+                                .filter(|&j| chunk[j] >= init + threshold)
+                                .map(|j| 8 * w + j),
+                        );
+                    }
+                }
+                for offset in candidates.drain(..) {
+                    let rel = trial_divide(n, &fb, &poly, &sp, start + offset, m);
+                    if let Some((rel, large)) = rel {
+                        if large == 1 {
+                            relations.push(rel);
+                        } else if let Some(other) = partials.remove(&large) {
+                            let mut factors = other.factors;
+                            factors.extend(rel.factors);
+                            relations.push(Relation {
+                                y: other.y * rel.y % n,
+                                factors,
+                                sqrt_extra: large.into(),
+                            });
+                        } else {
+                            partials.insert(large, rel);
+                        }
+                    }
+                }
+            }
+            if relations.len() >= wanted {
+                break;
+            }
+        }
+    }
+
+    find_dependencies(&relations, fb.len() + 1)
+        .iter()
+        .find_map(|dep| combine(n, &fb, &relations, dep))
+}
+
+// The factor base primes that are sieved for the current a, that is those
+// from SMALL_PRIME_SKIP up that do not divide a, in flat arrays. Primes
+// below SIEVE_BLOCK are sieved block by block; the larger ones hit a block
+// at most twice, so their hits are collected into per-block buckets once
+// per polynomial instead.
+#[derive(Default)]
+struct SievePrimes {
+    idx: Vec<usize>,
+    primes: Vec<u32>,
+    logp: Vec<u8>,
+    m_mod: Vec<u32>,
+    // p^-1 mod 2^32, to test divisibility by p with one multiplication.
+    inv: Vec<u32>,
+    // Number of primes below SIEVE_BLOCK.
+    medium: usize,
+    // Roots of the current polynomial as values of x mod p.
+    soln1: Vec<u32>,
+    soln2: Vec<u32>,
+    // 2 B_l a^-1 mod p, the root shift of each Gray code step.
+    bainv2: Vec<Vec<u32>>,
+    // Roots as positions in [0, 2M), and the next position to sieve.
+    root1: Vec<u32>,
+    root2: Vec<u32>,
+    next1: Vec<u32>,
+    next2: Vec<u32>,
+    // (offset in the block, index) for each hit of a large prime.
+    buckets: Vec<Vec<(u32, u32)>>,
+}
+
+impl SievePrimes {
+    fn reset(
+        &mut self,
+        fb: &FactorBase,
+        a_idx: &[usize],
+        a: &BigUint,
+        b: &BigUint,
+        bs: &[BigUint],
+        m: usize,
+    ) {
+        self.idx.clear();
+        self.primes.clear();
+        self.logp.clear();
+        self.m_mod.clear();
+        self.inv.clear();
+        self.soln1.clear();
+        self.soln2.clear();
+        self.bainv2.resize(bs.len(), vec![]);
+        for d in self.bainv2.iter_mut() {
+            d.clear();
+        }
+        let first = fb.primes.partition_point(|&p| p < SMALL_PRIME_SKIP);
+        for i in first..fb.len() {
+            if a_idx.contains(&i) {
+                continue;
+            }
+            let p = fb.primes[i] as u64;
+            let ainv = fb.inverse(i, (a % p).to_u64().unwrap());
+            let bm = (b % p).to_u64().unwrap();
+            let t = fb.sqrt[i] as u64;
+            self.idx.push(i);
+            self.primes.push(p as u32);
+            self.logp.push(fb.logp[i]);
+            self.m_mod.push((m as u64 % p) as u32);
+            // Newton's iteration doubles the correct low bits from 3.
+            let p = p as u32;
+            let mut inv = p;
+            for _ in 0..4 {
+                inv = inv.wrapping_mul(2u32.wrapping_sub(p.wrapping_mul(inv)));
+            }
+            self.inv.push(inv);
+            let p = p as u64;
+            self.soln1.push((ainv * ((t + p - bm) % p) % p) as u32);
+            self.soln2.push((ainv * ((2 * p - t - bm) % p) % p) as u32);
+            for (d, bl) in self.bainv2.iter_mut().zip(bs.iter()) {
+                d.push((2 * (bl % p).to_u64().unwrap() * ainv % p) as u32);
+            }
+        }
+        let len = self.primes.len();
+        self.medium = self.primes.partition_point(|&p| (p as usize) < SIEVE_BLOCK);
+        for v in [&mut self.root1, &mut self.root2] {
+            v.resize(len, 0);
+        }
+        for v in [&mut self.next1, &mut self.next2] {
+            v.resize(self.medium, 0);
+        }
+        self.buckets.resize(2 * m / SIEVE_BLOCK, vec![]);
+    }
+
+    fn next_b(&mut self, j: usize, negative: bool) {
+        let d = &self.bainv2[j];
+        for (x, &p) in self.primes.iter().enumerate() {
+            for s in [&mut self.soln1[x], &mut self.soln2[x]] {
+                *s = if negative {
+                    let t = *s + d[x];
+                    if t >= p {
+                        t - p
+                    } else {
+                        t
+                    }
+                } else if *s >= d[x] {
+                    *s - d[x]
+                } else {
+                    *s + p - d[x]
+                };
+            }
+        }
+    }
+
+    fn start_roots(&mut self) {
+        for (x, &p) in self.primes.iter().enumerate() {
+            let m_mod = self.m_mod[x];
+            let pos = |s: u32| {
+                let r = s + m_mod;
+                if r >= p {
+                    r - p
+                } else {
+                    r
+                }
+            };
+            self.root1[x] = pos(self.soln1[x]);
+            self.root2[x] = pos(self.soln2[x]);
+        }
+        self.next1.copy_from_slice(&self.root1[..self.medium]);
+        self.next2.copy_from_slice(&self.root2[..self.medium]);
+
+        for bucket in self.buckets.iter_mut() {
+            bucket.clear();
+        }
+        let end = (self.buckets.len() * SIEVE_BLOCK) as u32;
+        for x in self.medium..self.primes.len() {
+            for mut r in [self.root1[x], self.root2[x]] {
+                while r < end {
+                    let block = r as usize / SIEVE_BLOCK;
+                    self.buckets[block].push((r % SIEVE_BLOCK as u32, x as u32));
+                    r += self.primes[x];
+                }
+            }
+        }
+    }
+
+    // Adds log p at the positions of the block hit by p.
+    fn sieve_block(&mut self, sieve: &mut [u8], block: usize, init: u8) {
+        sieve.fill(init);
+        let start = (block * SIEVE_BLOCK) as u32;
+        let end = start + SIEVE_BLOCK as u32;
+        for x in 0..self.medium {
+            let (p, lp) = (self.primes[x], self.logp[x]);
+            let (mut r1, mut r2) = (self.next1[x], self.next2[x]);
+            if r1 > r2 {
+                std::mem::swap(&mut r1, &mut r2);
+            }
+            while r2 < end {
+                sieve[(r1 - start) as usize] = sieve[(r1 - start) as usize].saturating_add(lp);
+                sieve[(r2 - start) as usize] = sieve[(r2 - start) as usize].saturating_add(lp);
+                r1 += p;
+                r2 += p;
+            }
+            if r1 < end {
+                sieve[(r1 - start) as usize] = sieve[(r1 - start) as usize].saturating_add(lp);
+                r1 += p;
+            }
+            self.next1[x] = r1;
+            self.next2[x] = r2;
+        }
+        for &(offset, x) in self.buckets[block].iter() {
+            let v = &mut sieve[offset as usize];
+            *v = v.saturating_add(self.logp[x as usize]);
+        }
+    }
+
+    // The sieved primes dividing g at pos, by index.
+    fn hits(&self, pos: usize) -> impl Iterator<Item = usize> + '_ {
+        let medium = (0..self.medium).filter(move |&x| {
+            let p = self.primes[x];
+            let hit = |r: u32| (pos as u32 + p - r).wrapping_mul(self.inv[x]) <= u32::MAX / p;
+            hit(self.root1[x]) || hit(self.root2[x])
+        });
+        let offset = (pos % SIEVE_BLOCK) as u32;
+        let large = self.buckets[pos / SIEVE_BLOCK]
+            .iter()
+            .filter(move |&&(o, _)| o == offset)
+            .map(|&(_, x)| x as usize);
+        medium.chain(large)
+    }
+}
+
+// Factors g(pos - M) over the factor base. Returns the relation together
+// with its leftover large prime (1 for a full relation).
+fn trial_divide(
+    n: &BigUint,
+    fb: &FactorBase,
+    poly: &Polynomial,
+    sp: &SievePrimes,
+    pos: usize,
+    m: usize,
+) -> Option<(Relation, u64)> {
+    let x = BigInt::from(pos as i64 - m as i64);
+    let g: BigInt = (&poly.a * &x + &poly.b * 2) * &x + &poly.c;
+    let y = (&poly.a * &x + &poly.b).mod_floor(&BigInt::from_biguint(Sign::Plus, n.clone()));
+
+    let mut factors = vec![];
+    if g.is_negative() {
+        factors.push(0);
+    }
+    let mut v = g.abs().to_biguint().unwrap();
+    if v.is_zero() {
+        return None;
+    }
+    factors.extend(poly.a_idx.iter().map(|&i| i + 1));
+
+    let tz = v.trailing_zeros().unwrap();
+    v >>= tz;
+    factors.extend(std::iter::repeat_n(1, tz as usize));
+
+    let mut divide = |i: usize, p: u32| {
+        while (&v % p).is_zero() {
+            v /= p;
+            factors.push(i + 1);
+        }
+    };
+    // Unsieved primes are tried directly, sieved ones only where a root hits.
+    let small = fb.primes.partition_point(|&p| p < SMALL_PRIME_SKIP);
+    for i in (1..small).chain(poly.a_idx.iter().copied()) {
+        divide(i, fb.primes[i]);
+    }
+    for x in sp.hits(pos) {
+        divide(sp.idx[x], sp.primes[x]);
+    }
+
+    let large = v.to_u64()?;
+    let pmax = *fb.primes.last().unwrap() as u64;
+    if large != 1 && large > (pmax * LARGE_PRIME_MULTIPLIER).min(pmax * pmax) {
+        return None;
+    }
+    let y = y.to_biguint().unwrap();
+    Some((
+        Relation {
+            y,
+            factors,
+            sqrt_extra: BigUint::one(),
+        },
+        large,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn siqs_balanced_semiprime() {
+        let p = BigUint::from(1000000000000000003u64);
+        let q = BigUint::from(1000000000000000009u64);
+        let n = &p * &q;
+        let f = siqs(&n).unwrap();
+        assert!(f == p || f == q);
+    }
+
+    #[test]
+    fn hard_cofactors_use_siqs() {
+        use rand::{rngs::StdRng, SeedableRng};
+        // Two 60-bit primes, which the pre-passes leave to SIQS.
+        let p = 864691128455135281_u128;
+        let q = 864691128456135329_u128;
+        let f = siqs_with_rng(&(p * q).into(), &mut StdRng::seed_from_u64(0)).unwrap();
+        assert!(f == p.into() || f == q.into());
+        assert_eq!(factorization(p * q), [p, q]);
+
+        let p = BigUint::from(1180591620717411303449_u128);
+        let q = BigUint::from(1180591620717411303491_u128);
+        assert_eq!(factorize_biguint(&(&p * &q)), [p, q]);
+    }
+
+    #[test]
+    fn siqs_small_inputs() {
+        for &(n, p, q) in [(15u32, 3u32, 5u32), (21, 3, 7), (1009 * 1013, 1009, 1013)].iter() {
+            let f = siqs(&n.into()).unwrap();
+            assert!(f == p.into() || f == q.into());
+        }
+        for &n in [0u32, 1, 2, 3, 7, 997, 1000003].iter() {
+            assert_eq!(siqs(&n.into()), None);
+        }
+        // s = 1, so a runs through the primes outwards from the target.
+        for &(p, q) in [(10007u64, 10009u64), (3002101, 3003113), (3011377, 3012389)].iter() {
+            let f = siqs(&(p * q).into()).unwrap();
+            assert!(f == p.into() || f == q.into());
+        }
+    }
+}
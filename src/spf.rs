@@ -0,0 +1,202 @@
+use std::convert::TryFrom;
+
+use crate::{
+    is_prime_u64, pollard_rho,
+    primality::{icbrt_u64, isqrt_u128},
+    sieve::primes_in_range,
+    Factorization,
+};
+
+// factor_range factors this many numbers at a time.
+const FACTOR_BLOCK: u64 = 1 << 16;
+
+// Smallest prime factors up to a limit below 2^32. The smallest prime factor
+// of a composite is at most its square root, so it fits in 16 bits, and 0
+// marks the primes.
+pub struct SpfTable {
+    spf: Vec<u16>,
+}
+
+impl SpfTable {
+    // Linear sieve: every composite is crossed off exactly once, by its
+    // smallest prime factor.
+    pub fn new(limit: u64) -> Self {
+        assert!(limit < 1 << 32, "limit must be below 2^32");
+        let n = limit as usize;
+        let mut spf = vec![0u16; n + 1];
+        let mut primes: Vec<usize> = vec![];
+        for i in 2..=n {
+            let lp = match spf[i] {
+                0 => {
+                    primes.push(i);
+                    i
+                }
+                p => p as usize,
+            };
+            for &p in primes.iter() {
+                if p > lp || i * p > n {
+                    break;
+                }
+                spf[i * p] = p as u16;
+            }
+        }
+        SpfTable { spf }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.spf.len() as u64 - 1
+    }
+
+    // None for 0 and 1.
+    pub fn smallest_prime_factor(&self, n: u64) -> Option<u64> {
+        match self.spf[n as usize] {
+            _ if n < 2 => None,
+            0 => Some(n),
+            p => Some(p as u64),
+        }
+    }
+}
+
+pub fn factorize_with_table(n: u64, table: &SpfTable) -> Factorization {
+    assert!(n >= 1 && n <= table.limit(), "n must be in [1, limit]");
+    let mut primes = vec![];
+    let mut n = n;
+    while let Some(p) = table.smallest_prime_factor(n) {
+        primes.push(p as u128);
+        n /= p;
+    }
+    Factorization::from_primes(primes)
+}
+
+// (n, factorization of n) for n in [lo, hi), n > 0, by sieving each block
+// with the primes up to cbrt(hi). What is left of each n then has at most
+// two prime factors.
+pub fn factor_range(lo: u64, hi: u64) -> FactorRange {
+    let cbrt = icbrt_u64(hi.saturating_sub(1));
+    FactorRange {
+        next: lo.max(1),
+        hi,
+        primes: primes_in_range(0, cbrt + 1)
+            .map(|p| u32::try_from(p).unwrap())
+            .collect(),
+        block: vec![].into_iter(),
+    }
+}
+
+pub struct FactorRange {
+    next: u64,
+    hi: u64,
+    primes: Vec<u32>,
+    block: std::vec::IntoIter<(u64, Factorization)>,
+}
+
+impl FactorRange {
+    fn factor_block(&self, lo: u64, hi: u64) -> Vec<(u64, Factorization)> {
+        let mut rem: Vec<u64> = (lo..hi).collect();
+        let mut factors = vec![vec![]; rem.len()];
+        for &p in self.primes.iter() {
+            let p = p as u64;
+            let mut i = ((p - lo % p) % p) as usize;
+            while i < rem.len() {
+                while rem[i].is_multiple_of(p) {
+                    rem[i] /= p;
+                    factors[i].push(p as u128);
+                }
+                i += p as usize;
+            }
+        }
+        (lo..hi)
+            .zip(rem.into_iter().zip(factors))
+            .map(|(n, (r, mut f))| {
+                split_cofactor(r, &mut f);
+                (n, Factorization::from_primes(f))
+            })
+            .collect()
+    }
+}
+
+// r has no prime factor up to cbrt(n), so it is 1, a prime, or a product
+// of two primes.
+fn split_cofactor(r: u64, f: &mut Vec<u128>) {
+    if r == 1 {
+        return;
+    }
+    if is_prime_u64(r) {
+        f.push(r as u128);
+        return;
+    }
+    let s = isqrt_u128(r as u128);
+    let p = if s * s == r as u128 {
+        s
+    } else {
+        pollard_rho(r as u128)
+    };
+    f.push(p);
+    f.push(r as u128 / p);
+}
+
+impl Iterator for FactorRange {
+    type Item = (u64, Factorization);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.block.len() == 0 && self.next < self.hi {
+            let lo = self.next;
+            self.next = lo.saturating_add(FACTOR_BLOCK).min(self.hi);
+            self.block = self.factor_block(lo, self.next).into_iter();
+        }
+        self.block.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn spf_table() {
+        let table = SpfTable::new(100_000);
+        assert_eq!(table.limit(), 100_000);
+        assert_eq!(table.smallest_prime_factor(1), None);
+        assert_eq!(table.smallest_prime_factor(99991), Some(99991));
+        assert_eq!(table.smallest_prime_factor(317 * 313), Some(313));
+        for n in 1..=100_000u64 {
+            let f = factorize_with_table(n, &table);
+            assert_eq!(f.value(), Some(n as u128));
+            assert!(f.iter().all(|(p, _)| is_prime_u64(p as u64)));
+        }
+        assert_eq!(factorize_with_table(360, &table), factorize(360));
+    }
+
+    #[test]
+    fn factor_ranges() {
+        let small: Vec<_> = factor_range(0, 1000).collect();
+        assert_eq!(small.len(), 999);
+        for (n, f) in small {
+            assert_eq!(f, factorize(n as u128));
+        }
+
+        let windows = [
+            (1 << 40, 70_000),
+            (10u64.pow(15), 70_000),
+            (1 << 62, 1000),
+            (u64::MAX - 1000, 1000),
+        ];
+        for &(lo, len) in windows.iter() {
+            let mut count = 0;
+            for (n, f) in factor_range(lo, lo + len) {
+                assert_eq!(n, lo + count);
+                assert_eq!(f.value(), Some(n as u128));
+                assert!(f.iter().all(|(p, _)| is_prime_u64(p as u64)));
+                count += 1;
+            }
+            assert_eq!(count, len);
+        }
+        // Cofactors left over after sieving with two prime factors.
+        let p = 4294967291u64;
+        let q = 4294967279u64;
+        for &n in [p * p, p * q].iter() {
+            let (_, f) = factor_range(n, n + 1).next().unwrap();
+            assert_eq!(f, factorize(n as u128));
+        }
+    }
+}